@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS checkpoint_failures (
+    id INTEGER NOT NULL PRIMARY KEY,
+    checkpoint_sequence_number INTEGER NOT NULL,
+    transaction_digest TEXT,
+    object_id TEXT,
+    error TEXT NOT NULL,
+    action TEXT NOT NULL,
+    recorded_at_ms INTEGER NOT NULL
+);
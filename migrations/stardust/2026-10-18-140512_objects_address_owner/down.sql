@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX objects_address_owner;
+
+ALTER TABLE objects DROP COLUMN address_owner;
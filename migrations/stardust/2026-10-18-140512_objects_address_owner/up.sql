@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE objects ADD COLUMN address_owner BLOB;
+
+CREATE INDEX IF NOT EXISTS objects_address_owner ON objects (address_owner);
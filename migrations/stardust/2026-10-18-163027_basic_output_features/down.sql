@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX basic_output_features_sender;
+
+DROP INDEX basic_output_features_tag;
+
+DROP TABLE basic_output_features;
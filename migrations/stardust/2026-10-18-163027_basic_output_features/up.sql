@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS basic_output_features (
+    object_id BLOB NOT NULL PRIMARY KEY,
+    sender BLOB,
+    tag BLOB,
+    FOREIGN KEY (object_id) REFERENCES objects (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS basic_output_features_sender ON basic_output_features (sender);
+
+CREATE INDEX IF NOT EXISTS basic_output_features_tag ON basic_output_features (tag);
@@ -0,0 +1,533 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Administrative HTTP API.
+//!
+//! It is bound to a separate address from the public REST API so that it can
+//! be kept private to the operators.
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Request, State},
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+};
+use http::{StatusCode, header::AUTHORIZATION};
+use iota_data_ingestion_core::ProgressStore;
+use iota_types::messages_checkpoint::CheckpointSequenceNumber;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::{
+    config::ConfigFile,
+    db::{ConnectionPool, WalCheckpoint},
+    models::CheckpointFailure,
+    snapshot::{SnapshotMetadata, create_snapshot},
+    sync::{FailureStore, SqliteProgressStore, SyncControl, WORKER_TASK_NAME},
+    telemetry::{LOG_FILTER, LogFilterHandle},
+};
+
+const BACKUP_ROUTE: &str = "/backup";
+const LOG_FILTER_ROUTE: &str = "/log-filter";
+const PAUSE_ROUTE: &str = "/sync/pause";
+const RESUME_ROUTE: &str = "/sync/resume";
+const SKIP_ROUTE: &str = "/sync/skip";
+const REINDEX_ROUTE: &str = "/sync/reindex";
+const WAL_CHECKPOINT_ROUTE: &str = "/wal-checkpoint";
+const CONFIG_ROUTE: &str = "/config";
+const FAILURES_ROUTE: &str = "/failures";
+
+/// Max number of failures listed by the admin API.
+const MAX_LISTED_FAILURES: i64 = 100;
+
+#[derive(Clone)]
+pub(crate) struct AdminState {
+    pub(crate) objects_pool: ConnectionPool,
+    pub(crate) progress_store_pool: ConnectionPool,
+    pub(crate) backup_dir: PathBuf,
+    /// Prevents concurrent backups from racing on the same target directory.
+    backup_lock: Arc<Mutex<()>>,
+    pub(crate) control: SyncControl,
+    /// The effective configuration of the service, with secrets redacted.
+    pub(crate) config: Arc<ConfigFile>,
+}
+
+impl AdminState {
+    pub(crate) fn new(
+        objects_pool: ConnectionPool,
+        progress_store_pool: ConnectionPool,
+        backup_dir: PathBuf,
+        control: SyncControl,
+        config: ConfigFile,
+    ) -> Self {
+        Self {
+            objects_pool,
+            progress_store_pool,
+            backup_dir,
+            backup_lock: Default::default(),
+            control,
+            config: Arc::new(config),
+        }
+    }
+}
+
+/// Start the admin API service, requiring the token, which must be set.
+pub(crate) fn spawn_admin_server(
+    socket_addr: SocketAddr,
+    state: AdminState,
+    token: Option<String>,
+    cancel_token: CancellationToken,
+    tasks: &mut JoinSet<anyhow::Result<()>>,
+) -> anyhow::Result<()> {
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        anyhow::bail!("the admin API requires `--admin-token` to be set");
+    };
+
+    tasks.spawn(async move {
+        let listener = tokio::net::TcpListener::bind(socket_addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to bind to socket {socket_addr}: {e}"))?;
+
+        info!("Admin API listening on: {socket_addr}");
+
+        let app =
+            admin_router(state).layer(middleware::from_fn_with_state(Arc::from(token), authorize));
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                cancel_token.cancelled().await;
+                info!("Shutdown signal received.");
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("server encountered an error: {e}"))?;
+
+        Ok(())
+    });
+
+    Ok(())
+}
+
+fn admin_router(state: AdminState) -> Router {
+    Router::new()
+        .route(BACKUP_ROUTE, post(backup))
+        .route(LOG_FILTER_ROUTE, get(log_filter).put(set_log_filter))
+        .route(PAUSE_ROUTE, post(pause))
+        .route(RESUME_ROUTE, post(resume))
+        .route(SKIP_ROUTE, post(skip))
+        .route(REINDEX_ROUTE, post(reindex))
+        .route(WAL_CHECKPOINT_ROUTE, post(wal_checkpoint))
+        .route(CONFIG_ROUTE, get(config))
+        .route(FAILURES_ROUTE, get(failures))
+        .layer(Extension(state))
+}
+
+/// Reject the requests without the `Authorization: Bearer <token>` header.
+async fn authorize(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| tokens_match(provided.as_bytes(), token.as_bytes()));
+    if !authorized {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "missing or invalid admin token".to_owned(),
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+/// Compare the tokens in constant time, not to leak how much of the token was
+/// guessed through the response time.
+fn tokens_match(provided: &[u8], expected: &[u8]) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct BackupResponse {
+    pub(crate) path: PathBuf,
+    pub(crate) metadata: SnapshotMetadata,
+}
+
+/// Take an online snapshot of both databases into the configured backup
+/// directory.
+pub(crate) async fn backup(
+    Extension(state): Extension<AdminState>,
+) -> Result<Json<BackupResponse>, (StatusCode, String)> {
+    tokio::task::spawn_blocking(move || {
+        let Ok(_guard) = state.backup_lock.try_lock() else {
+            return Err((
+                StatusCode::CONFLICT,
+                "a backup is already in progress".to_owned(),
+            ));
+        };
+
+        create_snapshot(
+            &state.objects_pool,
+            &state.progress_store_pool,
+            &state.backup_dir,
+        )
+        .map(|(path, metadata)| Json(BackupResponse { path, metadata }))
+        .map_err(|e| {
+            error!("backup failed: {e}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("backup failed: {e}"),
+            )
+        })
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("backup task failed: {e}"),
+        )
+    })?
+}
+
+fn log_filter_handle() -> Result<&'static LogFilterHandle, (StatusCode, String)> {
+    LOG_FILTER.get().ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the log filter is not initialized".to_owned(),
+        )
+    })
+}
+
+/// Get the directives of the current log filter.
+pub(crate) async fn log_filter() -> Result<String, (StatusCode, String)> {
+    log_filter_handle()?
+        .current()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))
+}
+
+/// Replace the log filter with the directives of the plain text body, e.g.
+/// `info,rebased_stardust_indexer::sync=debug`.
+pub(crate) async fn set_log_filter(directives: String) -> Result<String, (StatusCode, String)> {
+    let handle = log_filter_handle()?;
+    handle
+        .set(directives.trim())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+    info!("Log filter changed to {}", directives.trim());
+    log_filter().await
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SyncStatusResponse {
+    pub(crate) paused: bool,
+}
+
+/// Pause the processing of the checkpoints, the checkpoint being processed
+/// is completed first.
+pub(crate) async fn pause(Extension(state): Extension<AdminState>) -> Json<SyncStatusResponse> {
+    if state.control.pause() {
+        info!("Checkpoint syncing paused");
+    }
+    Json(SyncStatusResponse { paused: true })
+}
+
+/// Resume the processing of the checkpoints.
+pub(crate) async fn resume(Extension(state): Extension<AdminState>) -> Json<SyncStatusResponse> {
+    if state.control.resume() {
+        info!("Checkpoint syncing resumed");
+    }
+    Json(SyncStatusResponse { paused: false })
+}
+
+/// Resume the processing of the checkpoints, skipping the objects that could
+/// not be indexed in the ones it halted on, whatever the bad object policy.
+pub(crate) async fn skip(Extension(state): Extension<AdminState>) -> Json<SyncStatusResponse> {
+    if state.control.skip_bad_objects() {
+        info!("Checkpoint syncing resumed, skipping the bad objects it halted on");
+    }
+    Json(SyncStatusResponse { paused: false })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ReindexRequest {
+    /// The checkpoint to restart the syncing from.
+    pub(crate) from_checkpoint: CheckpointSequenceNumber,
+}
+
+/// Restart the syncing from an already indexed checkpoint, once the
+/// checkpoints being processed are completed.
+pub(crate) async fn reindex(
+    Extension(state): Extension<AdminState>,
+    Json(request): Json<ReindexRequest>,
+) -> Result<(StatusCode, Json<ReindexRequest>), (StatusCode, String)> {
+    let watermark = SqliteProgressStore::new(state.progress_store_pool)
+        .load(WORKER_TASK_NAME.to_owned())
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to load the syncing progress: {e}"),
+            )
+        })?;
+    if request.from_checkpoint > watermark {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("cannot skip checkpoints, the syncing is at checkpoint {watermark}"),
+        ));
+    }
+
+    info!(
+        "Reindex from checkpoint {} requested",
+        request.from_checkpoint
+    );
+    state.control.request_reindex(request.from_checkpoint);
+    Ok((StatusCode::ACCEPTED, Json(request)))
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct WalCheckpointResponse {
+    pub(crate) objects: WalCheckpoint,
+    pub(crate) progress_store: WalCheckpoint,
+}
+
+/// Copy the write-ahead logs into both databases and truncate them.
+pub(crate) async fn wal_checkpoint(
+    Extension(state): Extension<AdminState>,
+) -> Result<Json<WalCheckpointResponse>, (StatusCode, String)> {
+    tokio::task::spawn_blocking(move || {
+        Ok(Json(WalCheckpointResponse {
+            objects: state.objects_pool.wal_checkpoint()?,
+            progress_store: state.progress_store_pool.wal_checkpoint()?,
+        }))
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("WAL checkpoint task failed: {e}"),
+        )
+    })?
+    .map_err(|e: anyhow::Error| {
+        error!("WAL checkpoint failed: {e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+/// Get the effective configuration of the service, with secrets redacted.
+pub(crate) async fn config(Extension(state): Extension<AdminState>) -> Json<ConfigFile> {
+    Json(ConfigFile::clone(&state.config))
+}
+
+/// List the latest failures of the checkpoint processing, most recent first.
+pub(crate) async fn failures(
+    Extension(state): Extension<AdminState>,
+) -> Result<Json<Vec<CheckpointFailure>>, (StatusCode, String)> {
+    tokio::task::spawn_blocking(move || {
+        FailureStore::new(state.progress_store_pool).latest(MAX_LISTED_FAILURES)
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failures task failed: {e}"),
+        )
+    })?
+    .map(Json)
+    .map_err(|e| {
+        error!("failed to list the failures: {e}");
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        db::Name, rest::routes::test_utils::get_free_port_for_testing_only,
+        sync::SqliteProgressStore,
+    };
+
+    #[tokio::test]
+    async fn admin_api_cannot_run_without_token() -> Result<(), anyhow::Error> {
+        let test_db = "admin_api_without_token.db";
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db)?;
+        }
+        let pool = ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects)?;
+
+        let mut tasks = JoinSet::new();
+        for token in [None, Some(String::new())] {
+            let spawned = spawn_admin_server(
+                "127.0.0.1:0".parse()?,
+                AdminState::new(
+                    pool.clone(),
+                    pool.clone(),
+                    "admin_api_backups".into(),
+                    Default::default(),
+                    Default::default(),
+                ),
+                token,
+                CancellationToken::new(),
+                &mut tasks,
+            );
+            assert!(spawned.is_err());
+        }
+        assert!(tasks.is_empty());
+
+        drop(pool);
+        std::fs::remove_file(test_db)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn admin_api_requires_token_and_controls_syncing() -> Result<(), anyhow::Error> {
+        let objects_db = "admin_api_objects.db";
+        let progress_store_db = "admin_api_progress_store.db";
+        for db in [objects_db, progress_store_db] {
+            if Path::new(db).exists() {
+                std::fs::remove_file(db)?;
+            }
+        }
+
+        let objects_pool =
+            ConnectionPool::new_with_url(objects_db, Default::default(), Name::Objects)?;
+        objects_pool.run_migrations()?;
+        let progress_store_pool = ConnectionPool::new_with_url(
+            progress_store_db,
+            Default::default(),
+            Name::ProgressStore,
+        )?;
+        progress_store_pool.run_migrations()?;
+        SqliteProgressStore::new(progress_store_pool.clone())
+            .save(WORKER_TASK_NAME.to_owned(), 100)
+            .await?;
+
+        let control = SyncControl::default();
+        let cancel_token = CancellationToken::new();
+        let mut tasks = JoinSet::new();
+        let port = get_free_port_for_testing_only().unwrap();
+        spawn_admin_server(
+            format!("127.0.0.1:{port}").parse()?,
+            AdminState::new(
+                objects_pool,
+                progress_store_pool,
+                "admin_api_backups".into(),
+                control.clone(),
+                Default::default(),
+            ),
+            Some("secret".to_owned()),
+            cancel_token.clone(),
+            &mut tasks,
+        )?;
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        let client = reqwest::Client::new();
+        let url = |route: &str| format!("http://127.0.0.1:{port}{route}");
+
+        let resp = client.post(url(PAUSE_ROUTE)).send().await?;
+        assert_eq!(resp.status(), 401);
+        let resp = client
+            .post(url(PAUSE_ROUTE))
+            .bearer_auth("wrong")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 401);
+
+        let resp = client
+            .post(url(PAUSE_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.json::<SyncStatusResponse>().await?.paused);
+        assert!(!control.pause());
+
+        let resp = client
+            .post(url(RESUME_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert!(!resp.json::<SyncStatusResponse>().await?.paused);
+        assert!(control.pause());
+
+        let skips = control.skip_requests();
+        let resp = client
+            .post(url(SKIP_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert!(!resp.json::<SyncStatusResponse>().await?.paused);
+        assert_eq!(control.skip_requests(), skips + 1);
+        assert!(control.pause());
+
+        // Checkpoints cannot be skipped
+        let resp = client
+            .post(url(REINDEX_ROUTE))
+            .bearer_auth("secret")
+            .json(&ReindexRequest {
+                from_checkpoint: 101,
+            })
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 400);
+
+        let resp = client
+            .post(url(REINDEX_ROUTE))
+            .bearer_auth("secret")
+            .json(&ReindexRequest { from_checkpoint: 0 })
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 202);
+        assert_eq!(control.reindex_requested().await, 0);
+
+        let resp = client
+            .post(url(WAL_CHECKPOINT_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 200);
+
+        let resp = client
+            .get(url(CONFIG_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 200);
+
+        let failures = client
+            .get(url(FAILURES_ROUTE))
+            .bearer_auth("secret")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<serde_json::Value>>()
+            .await?;
+        assert!(failures.is_empty());
+
+        cancel_token.cancel();
+        while let Some(result) = tasks.join_next().await {
+            result??;
+        }
+
+        for db in [objects_db, progress_store_db] {
+            std::fs::remove_file(db)?;
+        }
+
+        Ok(())
+    }
+}
@@ -2,18 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! Database related logic.
-use std::{env, time::Duration};
+use std::{path::Path, time::Duration};
 
 use anyhow::{Result, anyhow};
-use clap::Args;
+use clap::{ArgAction, Args, builder::BoolishValueParser};
 use diesel::{
     connection::SimpleConnection,
+    migration::MigrationSource,
     prelude::*,
     r2d2::{ConnectionManager, Pool, PooledConnection},
     sqlite::Sqlite,
 };
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
-use dotenvy::dotenv;
+use serde::Serialize;
 
 pub const STARDUST_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/stardust");
 pub const PROGRESS_STORE_MIGRATIONS: EmbeddedMigrations =
@@ -28,8 +29,10 @@ pub struct ConnectionPoolConfig {
     #[arg(long, value_parser = parse_duration, default_value = "30")]
     #[arg(env = "DB_CONNECTION_TIMEOUT_SECS")]
     pub connection_timeout_secs: Duration,
-    /// Enable WAL mode in the database.
-    #[arg(long)]
+    /// Enable WAL mode in the database, `--enable-wal=false` disables it if
+    /// enabled by the configuration file.
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, require_equals = true)]
+    #[arg(default_value_t = false, default_missing_value = "true", value_parser = BoolishValueParser::new())]
     pub enable_wal: bool,
 }
 
@@ -86,7 +89,67 @@ impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// The URLs of the databases.
+#[derive(Args, Debug, Clone, Default)]
+pub struct DatabaseConfig {
+    /// The SQLite database URL used to store the migrated objects.
+    #[arg(long, global = true, env = "OBJECTS_DB_URL")]
+    pub objects_db_url: Option<String>,
+    /// The SQLite database URL used to store the syncing progress.
+    #[arg(long, global = true, env = "PROGRESS_STORE_DB_URL")]
+    pub progress_store_db_url: Option<String>,
+}
+
+impl DatabaseConfig {
+    /// Build a new pool of connections to the given database.
+    pub fn connection_pool(
+        &self,
+        pool_config: ConnectionPoolConfig,
+        db_name: Name,
+    ) -> Result<ConnectionPool> {
+        ConnectionPool::new_with_url(self.db_url(db_name)?, pool_config, db_name)
+    }
+
+    /// Build a new pool of read-only connections to the given database.
+    pub fn read_only_connection_pool(
+        &self,
+        pool_config: ConnectionPoolConfig,
+        db_name: Name,
+    ) -> Result<ConnectionPool> {
+        ConnectionPool::new_with_url(&read_only_url(self.db_url(db_name)?), pool_config, db_name)
+    }
+
+    fn db_url(&self, db_name: Name) -> Result<&str> {
+        let (db_url, flag) = match db_name {
+            Name::Objects => (&self.objects_db_url, "objects-db-url"),
+            Name::ProgressStore => (&self.progress_store_db_url, "progress-store-db-url"),
+        };
+        db_url.as_deref().ok_or_else(|| {
+            anyhow!("the database URL must be set through `--{flag}`, the environment or the config file")
+        })
+    }
+}
+
+/// Turn a SQLite database URL into a URI opening the database in read-only
+/// mode.
+fn read_only_url(db_url: &str) -> String {
+    let uri = match db_url.strip_prefix("sqlite://") {
+        Some(path) => format!("file:{path}"),
+        None if db_url.starts_with("file:") => db_url.to_owned(),
+        // Escape the characters with a special meaning in URIs
+        None => format!(
+            "file:{}",
+            db_url
+                .replace('%', "%25")
+                .replace('?', "%3f")
+                .replace('#', "%23")
+        ),
+    };
+    let separator = if uri.contains('?') { '&' } else { '?' };
+    format!("{uri}{separator}mode=ro")
+}
+
+#[derive(Debug, Copy, Clone, clap::ValueEnum)]
 pub enum Name {
     Objects,
     ProgressStore,
@@ -99,25 +162,10 @@ pub enum Name {
 pub struct ConnectionPool {
     pool: Pool<ConnectionManager<SqliteConnection>>,
     db_name: Name,
+    db_url: String,
 }
 
 impl ConnectionPool {
-    /// Build a new pool of connections.
-    ///
-    /// Resolves the database URL from the environment.
-    pub fn new(pool_config: ConnectionPoolConfig, db_name: Name) -> Result<Self> {
-        dotenv().ok();
-
-        let db_url_env_var = match db_name {
-            Name::Objects => "OBJECTS_DB_URL",
-            Name::ProgressStore => "PROGRESS_STORE_DB_URL",
-        };
-
-        let database_url = env::var(db_url_env_var)
-            .unwrap_or_else(|_| panic!("the {db_url_env_var} environment variable must be set"));
-        Self::new_with_url(&database_url, pool_config, db_name)
-    }
-
     /// Build a new pool of connections to the given URL.
     pub fn new_with_url(
         db_url: &str,
@@ -136,6 +184,7 @@ impl ConnectionPool {
                     anyhow!("failed to initialize connection pool for {db_url} with error: {e:?}")
                 })?,
             db_name,
+            db_url: db_url.to_owned(),
         })
     }
 
@@ -162,6 +211,127 @@ impl ConnectionPool {
     pub fn revert_all_migrations(&self) -> Result<()> {
         revert_all_migrations(&mut self.get_connection()?, self.migrations())
     }
+
+    /// Get the version of the latest applied migration, if any.
+    pub fn latest_migration_version(&self) -> Result<Option<String>> {
+        latest_migration_version(&mut self.get_connection()?)
+    }
+
+    /// Get the version of the latest migration known to this binary.
+    pub fn latest_known_migration_version(&self) -> Result<Option<String>> {
+        latest_known_migration_version(self.migrations())
+    }
+
+    /// List the known and applied migrations, ordered by version.
+    pub fn migrations_status(&self) -> Result<Vec<MigrationStatus>> {
+        migrations_status(&mut self.get_connection()?, self.migrations())
+    }
+
+    /// Revert the applied migrations newer than `version`, returning the
+    /// reverted versions.
+    pub fn revert_migrations_to(&self, version: &str) -> Result<Vec<String>> {
+        revert_migrations_to(&mut self.get_connection()?, self.migrations(), version)
+    }
+
+    /// Ensure the database has no applied migration newer than the ones known
+    /// to this binary.
+    pub fn check_migrations_compatibility(&self) -> Result<()> {
+        let Some(latest_known) = self.latest_known_migration_version()? else {
+            return Ok(());
+        };
+
+        let unknown = self
+            .migrations_status()?
+            .into_iter()
+            .filter(|status| status.name.is_none() && status.version > latest_known)
+            .map(|status| status.version)
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            anyhow::bail!(
+                "the {:?} database has migrations {unknown:?} newer than the latest known {latest_known}, upgrade the binary",
+                self.db_name
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Copy the database into `destination` using the SQLite online backup
+    /// API.
+    ///
+    /// The copy is taken in a single step, so it represents a consistent
+    /// snapshot even if other connections keep writing to the database.
+    pub fn backup_to(&self, destination: &Path) -> Result<()> {
+        let source = rusqlite::Connection::open_with_flags(
+            &self.db_url,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_URI,
+        )
+        .map_err(|e| anyhow!("failed to open {} for backup: {e}", self.db_url))?;
+        let mut destination_conn = rusqlite::Connection::open(destination).map_err(|e| {
+            anyhow!(
+                "failed to create backup file {}: {e}",
+                destination.display()
+            )
+        })?;
+
+        copy_database(&source, &mut destination_conn)
+            .map_err(|e| anyhow!("failed to back up {}: {e}", self.db_url))
+    }
+
+    /// Replace the content of the database with the one of the `source` file
+    /// using the SQLite online backup API.
+    pub fn restore_from(&self, source: &Path) -> Result<()> {
+        let source_conn = rusqlite::Connection::open_with_flags(
+            source,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .map_err(|e| anyhow!("failed to open {} for restore: {e}", source.display()))?;
+        let mut destination = rusqlite::Connection::open(&self.db_url)
+            .map_err(|e| anyhow!("failed to open {} for restore: {e}", self.db_url))?;
+
+        copy_database(&source_conn, &mut destination)
+            .map_err(|e| anyhow!("failed to restore {}: {e}", self.db_url))
+    }
+
+    /// Copy the content of the write-ahead log into the database and truncate
+    /// the log.
+    pub fn wal_checkpoint(&self) -> Result<WalCheckpoint> {
+        diesel::sql_query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .get_result(&mut self.get_connection()?)
+            .map_err(|e| anyhow!("failed to checkpoint the WAL of {}: {e}", self.db_url))
+    }
+}
+
+/// The outcome of a WAL checkpoint, all the counts are `-1` if the database is
+/// not in WAL mode.
+#[derive(Debug, Clone, Copy, QueryableByName, Serialize)]
+pub struct WalCheckpoint {
+    /// Whether the checkpoint could not complete because of concurrent
+    /// connections.
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub busy: bool,
+    /// The number of frames in the log.
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub log: i32,
+    /// The number of frames copied into the database.
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub checkpointed: i32,
+}
+
+/// Copy a whole database with the SQLite online backup API.
+fn copy_database(
+    source: &rusqlite::Connection,
+    destination: &mut rusqlite::Connection,
+) -> rusqlite::Result<()> {
+    let backup = rusqlite::backup::Backup::new(source, destination)?;
+
+    // A negative page count copies the whole database in a single step
+    loop {
+        match backup.step(-1)? {
+            rusqlite::backup::StepResult::Done => return Ok(()),
+            _ => std::thread::sleep(Duration::from_millis(100)),
+        }
+    }
 }
 
 /// Run any pending migrations to the connected database.
@@ -188,10 +358,115 @@ pub fn revert_all_migrations(
     Ok(())
 }
 
+/// The state of a migration in the connected database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: String,
+    /// The full name of the migration, `None` if it is unknown to this binary.
+    pub name: Option<String>,
+    pub applied: bool,
+}
+
+/// List the known and applied migrations of the connected database.
+pub fn migrations_status(
+    connection: &mut impl MigrationHarness<Sqlite>,
+    migrations: EmbeddedMigrations,
+) -> Result<Vec<MigrationStatus>> {
+    let applied = connection
+        .applied_migrations()
+        .map_err(|e| anyhow!("failed to read applied migrations {e}"))?;
+    let known = MigrationSource::<Sqlite>::migrations(&migrations)
+        .map_err(|e| anyhow!("failed to read embedded migrations {e}"))?;
+
+    let mut status = known
+        .iter()
+        .map(|migration| {
+            let version = migration.name().version().as_owned();
+            MigrationStatus {
+                applied: applied.contains(&version),
+                version: version.to_string(),
+                name: Some(migration.name().to_string()),
+            }
+        })
+        .collect::<Vec<_>>();
+    status.extend(
+        applied
+            .iter()
+            .filter(|version| {
+                !known
+                    .iter()
+                    .any(|migration| migration.name().version() == **version)
+            })
+            .map(|version| MigrationStatus {
+                version: version.to_string(),
+                name: None,
+                applied: true,
+            }),
+    );
+    status.sort_by(|a, b| a.version.cmp(&b.version));
+
+    Ok(status)
+}
+
+/// Revert the migrations applied to the connected database that are newer
+/// than the given known `version`.
+pub fn revert_migrations_to(
+    connection: &mut impl MigrationHarness<Sqlite>,
+    migrations: EmbeddedMigrations,
+    version: &str,
+) -> Result<Vec<String>> {
+    let known = MigrationSource::<Sqlite>::migrations(&migrations)
+        .map_err(|e| anyhow!("failed to read embedded migrations {e}"))?;
+    if !known
+        .iter()
+        .any(|migration| migration.name().version().to_string() == version)
+    {
+        anyhow::bail!("unknown migration version {version}");
+    }
+
+    let mut reverted = Vec::new();
+    while let Some(latest) = latest_migration_version(connection)? {
+        if latest.as_str() <= version {
+            break;
+        }
+        let migration = known
+            .iter()
+            .find(|migration| migration.name().version().to_string() == latest)
+            .ok_or_else(|| anyhow!("cannot revert unknown migration {latest}"))?;
+        connection
+            .revert_migration(migration.as_ref())
+            .map_err(|e| anyhow!("failed to revert migration {latest} {e}"))?;
+        reverted.push(latest);
+    }
+
+    Ok(reverted)
+}
+
+/// Get the version of the latest migration applied to the connected database.
+pub fn latest_migration_version(
+    connection: &mut impl MigrationHarness<Sqlite>,
+) -> Result<Option<String>> {
+    let applied = connection
+        .applied_migrations()
+        .map_err(|e| anyhow!("failed to read applied migrations {e}"))?;
+
+    Ok(applied.into_iter().max().map(|version| version.to_string()))
+}
+
+/// Get the version of the latest migration embedded in the binary.
+pub fn latest_known_migration_version(migrations: EmbeddedMigrations) -> Result<Option<String>> {
+    let known = MigrationSource::<Sqlite>::migrations(&migrations)
+        .map_err(|e| anyhow!("failed to read embedded migrations {e}"))?;
+
+    Ok(known
+        .iter()
+        .map(|migration| migration.name().version().as_owned())
+        .max()
+        .map(|version| version.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
-
     use super::*;
 
     #[test]
@@ -225,4 +500,51 @@ mod tests {
         // clean-up test db
         std::fs::remove_file(test_db).unwrap();
     }
+
+    #[test]
+    fn migrations_status_and_compatibility() {
+        let test_db = "migrations_status_and_compatibility.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        let latest_known = pool.latest_known_migration_version().unwrap().unwrap();
+
+        let status = pool.migrations_status().unwrap();
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|s| !s.applied && s.name.is_some()));
+
+        pool.run_migrations().unwrap();
+        let status = pool.migrations_status().unwrap();
+        assert!(status.iter().all(|s| s.applied));
+        pool.check_migrations_compatibility().unwrap();
+
+        // Reverting to the latest version is a no-op, unknown versions are rejected
+        assert!(pool.revert_migrations_to(&latest_known).unwrap().is_empty());
+        assert!(pool.revert_migrations_to("19700101000000").is_err());
+
+        // Simulate a migration applied by a newer binary
+        pool.get_connection()
+            .unwrap()
+            .batch_execute(
+                "INSERT INTO __diesel_schema_migrations (version) VALUES ('99991231235959');",
+            )
+            .unwrap();
+        let status = pool.migrations_status().unwrap();
+        assert_eq!(
+            status.last(),
+            Some(&MigrationStatus {
+                version: "99991231235959".to_owned(),
+                name: None,
+                applied: true,
+            })
+        );
+        assert!(pool.check_migrations_compatibility().is_err());
+
+        // clean-up test db
+        std::fs::remove_file(test_db).unwrap();
+    }
 }
@@ -0,0 +1,312 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decoding of raw objects the same way as the `CheckpointWorker`, to tell
+//! what the Indexer would store for them.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use clap::{ArgGroup, Args, ValueEnum};
+use iota_types::{base_types::ObjectID, full_checkpoint_content::CheckpointData, object::Object};
+use serde::Serialize;
+
+use crate::{
+    models::{IotaAddress, ObjectType, StoredObject},
+    query::QueriedOutput,
+    rest::routes::v1::responses::Expiration,
+};
+
+/// Leading byte of the checkpoint files holding BCS encoded `CheckpointData`.
+const CHECKPOINT_FILE_BCS_ENCODING: u8 = 1;
+
+#[derive(Args, Debug, Clone)]
+#[clap(group(ArgGroup::new("input").required(true)))]
+pub struct InspectArgs {
+    /// Hex encoded BCS bytes, with or without the `0x` prefix.
+    #[clap(long, group = "input")]
+    pub hex: Option<String>,
+    /// Base64 encoded BCS bytes.
+    #[clap(long, group = "input")]
+    pub base64: Option<String>,
+    /// A checkpoint file, as found in the checkpoint stores.
+    #[clap(long, group = "input", requires = "object_id")]
+    pub checkpoint_file: Option<PathBuf>,
+    /// The object of the checkpoint file to inspect.
+    #[clap(long, requires = "checkpoint_file")]
+    pub object_id: Option<ObjectID>,
+    /// What the BCS bytes encode.
+    #[clap(long, value_enum, default_value = "object")]
+    pub content: BcsContent,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum BcsContent {
+    /// A whole object, as returned by a fullnode.
+    Object,
+    /// The contents of a `BasicOutput`, as stored in the database.
+    Basic,
+    /// The contents of an `NftOutput`, as stored in the database.
+    Nft,
+}
+
+/// What the Indexer would make of an object.
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct InspectionReport {
+    pub(crate) object_type: Option<String>,
+    /// The owner of an address-owned object.
+    pub(crate) address_owner: Option<String>,
+    pub(crate) output: Option<QueriedOutput>,
+    pub(crate) expiration_unlock_condition: Option<Expiration>,
+    /// Why the object would not be stored, if so.
+    pub(crate) rejection: Option<String>,
+}
+
+impl InspectionReport {
+    fn rejected(self, reason: impl ToString) -> Self {
+        Self {
+            rejection: Some(reason.to_string()),
+            ..self
+        }
+    }
+}
+
+/// Decode the object given by the arguments.
+pub(crate) fn inspect(args: &InspectArgs) -> Result<InspectionReport> {
+    let bytes = match (&args.hex, &args.base64, &args.checkpoint_file) {
+        (Some(hex), _, _) => {
+            hex::decode(hex.strip_prefix("0x").unwrap_or(hex)).context("invalid hex input")?
+        }
+        (_, Some(base64), _) => BASE64.decode(base64).context("invalid base64 input")?,
+        (_, _, Some(path)) => {
+            let object_id = args
+                .object_id
+                .context("an object ID is required to inspect a checkpoint file")?;
+            let object = find_checkpoint_object(&read_checkpoint_file(path)?, object_id)?;
+            return Ok(inspect_object(object));
+        }
+        _ => anyhow::bail!("no input to inspect"),
+    };
+
+    Ok(match args.content {
+        BcsContent::Object => {
+            inspect_object(bcs::from_bytes(&bytes).context("invalid BCS encoded object")?)
+        }
+        BcsContent::Basic => inspect_contents(ObjectType::Basic, bytes),
+        BcsContent::Nft => inspect_contents(ObjectType::Nft, bytes),
+    })
+}
+
+/// Run the object through the same conversions as the `CheckpointWorker`.
+fn inspect_object(object: Object) -> InspectionReport {
+    match StoredObject::try_from(object) {
+        Ok(stored_object) => inspect_stored_object(stored_object),
+        Err(e) => InspectionReport::default().rejected(e),
+    }
+}
+
+fn inspect_contents(object_type: ObjectType, contents: Vec<u8>) -> InspectionReport {
+    inspect_stored_object(StoredObject {
+        // Not needed for decoding, the id is taken from the contents
+        id: IotaAddress(iota_types::base_types::IotaAddress::ZERO),
+        object_type,
+        contents,
+        address_owner: None,
+    })
+}
+
+fn inspect_stored_object(stored_object: StoredObject) -> InspectionReport {
+    let report = InspectionReport {
+        object_type: Some(format!("{:?}", stored_object.object_type)),
+        address_owner: stored_object
+            .address_owner
+            .map(|address_owner| address_owner.0.to_string()),
+        ..Default::default()
+    };
+
+    let output = match QueriedOutput::try_from(stored_object.clone()) {
+        Ok(output) => output,
+        Err(e) => return report.rejected(format!("undecodable contents: {e}")),
+    };
+    let report = InspectionReport {
+        output: Some(output),
+        ..report
+    };
+
+    match stored_object.expiration_unlock_condition() {
+        Ok(condition) => InspectionReport {
+            expiration_unlock_condition: condition.map(|condition| Expiration {
+                owner: condition.owner.0.to_string(),
+                return_address: condition.return_address.0.to_string(),
+                unix_time: condition.unix_time as u64,
+            }),
+            ..report
+        },
+        Err(e) => report.rejected(e),
+    }
+}
+
+/// Read a checkpoint file, as written by the checkpoint stores.
+fn read_checkpoint_file(path: &Path) -> Result<CheckpointData> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("failed to read checkpoint file {}", path.display()))?;
+    match bytes.split_first() {
+        Some((&CHECKPOINT_FILE_BCS_ENCODING, data)) => bcs::from_bytes(data)
+            .with_context(|| format!("invalid checkpoint file {}", path.display())),
+        _ => anyhow::bail!("unsupported encoding of checkpoint file {}", path.display()),
+    }
+}
+
+/// Find the object among the ones created or mutated by the checkpoint.
+fn find_checkpoint_object(checkpoint: &CheckpointData, object_id: ObjectID) -> Result<Object> {
+    checkpoint
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.output_objects.iter())
+        .find(|object| object.id() == object_id)
+        .cloned()
+        .with_context(|| {
+            format!(
+                "object {object_id} is not an output of checkpoint {}",
+                checkpoint.checkpoint_summary.sequence_number
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use iota_types::{
+        balance::Balance,
+        base_types::SequenceNumber,
+        collection_types::Bag,
+        digests::TransactionDigest,
+        gas_coin::GAS,
+        id::UID,
+        object::{Data, MoveObject, Owner},
+        stardust::output::{
+            basic::BasicOutput, unlock_conditions::ExpirationUnlockCondition as OutputExpiration,
+        },
+        supported_protocol_versions::ProtocolConfig,
+    };
+
+    use super::*;
+
+    fn basic_output(expiration: Option<OutputExpiration>) -> BasicOutput {
+        BasicOutput {
+            id: UID::new(ObjectID::random()),
+            balance: Balance::new(100),
+            native_tokens: Bag::default(),
+            storage_deposit_return: None,
+            timelock: None,
+            expiration,
+            metadata: None,
+            tag: None,
+            sender: None,
+        }
+    }
+
+    fn basic_object(basic: &BasicOutput) -> Object {
+        let move_object = MoveObject::new_from_execution(
+            BasicOutput::tag(GAS::type_tag()).into(),
+            SequenceNumber::default(),
+            bcs::to_bytes(basic).unwrap(),
+            &ProtocolConfig::get_for_min_version(),
+        )
+        .unwrap();
+        Object::new_from_genesis(
+            Data::Move(move_object),
+            Owner::Shared {
+                initial_shared_version: SequenceNumber::default(),
+            },
+            TransactionDigest::default(),
+        )
+    }
+
+    fn args() -> InspectArgs {
+        InspectArgs {
+            hex: None,
+            base64: None,
+            checkpoint_file: None,
+            object_id: None,
+            content: BcsContent::Object,
+        }
+    }
+
+    #[test]
+    fn inspect_encoded_objects() {
+        let owner = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = basic_output(Some(OutputExpiration {
+            owner,
+            return_address: owner,
+            unix_time: 100,
+        }));
+
+        let report = inspect(&InspectArgs {
+            hex: Some(format!(
+                "0x{}",
+                hex::encode(bcs::to_bytes(&basic_object(&basic)).unwrap())
+            )),
+            ..args()
+        })
+        .unwrap();
+        assert_eq!(report.object_type.as_deref(), Some("Basic"));
+        assert_eq!(
+            report.output,
+            Some(QueriedOutput::Basic(basic.clone().into()))
+        );
+        assert_eq!(report.expiration_unlock_condition.unwrap().unix_time, 100);
+        assert!(report.rejection.is_none());
+
+        // The stored contents decode to the same output
+        let report = inspect(&InspectArgs {
+            base64: Some(BASE64.encode(bcs::to_bytes(&basic).unwrap())),
+            content: BcsContent::Basic,
+            ..args()
+        })
+        .unwrap();
+        assert_eq!(report.output, Some(QueriedOutput::Basic(basic.into())));
+
+        // Outputs without expiration unlock condition are stored as well
+        let report = inspect(&InspectArgs {
+            base64: Some(BASE64.encode(bcs::to_bytes(&basic_output(None)).unwrap())),
+            content: BcsContent::Basic,
+            ..args()
+        })
+        .unwrap();
+        assert!(report.output.is_some());
+        assert!(report.expiration_unlock_condition.is_none());
+        assert!(report.rejection.is_none());
+
+        // Contents of the wrong type
+        let report = inspect(&InspectArgs {
+            hex: Some("0102".to_owned()),
+            content: BcsContent::Nft,
+            ..args()
+        })
+        .unwrap();
+        assert_eq!(report.object_type.as_deref(), Some("Nft"));
+        assert!(report.output.is_none());
+        assert!(
+            report
+                .rejection
+                .unwrap()
+                .starts_with("undecodable contents")
+        );
+    }
+
+    #[test]
+    fn reject_unknown_checkpoint_file_encoding() {
+        let checkpoint_file = "reject_unknown_checkpoint_file_encoding.chk";
+        std::fs::write(checkpoint_file, [0, 1, 2]).unwrap();
+
+        let error = inspect(&InspectArgs {
+            checkpoint_file: Some(checkpoint_file.into()),
+            object_id: Some(ObjectID::random()),
+            ..args()
+        })
+        .unwrap_err();
+        assert!(error.to_string().starts_with("unsupported encoding"));
+
+        std::fs::remove_file(checkpoint_file).unwrap();
+    }
+}
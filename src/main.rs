@@ -1,29 +1,42 @@
 // Copyright (c) 2024 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use clap::{Parser, Subcommand};
-use db::{ConnectionPool, ConnectionPoolConfig, Name};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use db::{ConnectionPool, ConnectionPoolConfig, DatabaseConfig, Name};
+use dotenvy::dotenv;
 use tokio::{
     signal::unix::{SignalKind, signal},
     task::JoinHandle,
 };
-use tracing::{Level, error, info};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{error, info};
 use utoipa::OpenApi;
 
 use crate::{
-    rest::{ApiDoc, spawn_rest_server},
-    sync::{Indexer, IndexerConfig},
+    config::{ConfigFile, ServiceConfig},
+    rest::{ApiDoc, ApiKeys, RESPONSE_CACHE, ResponseCache, spawn_rest_server},
+    sync::Indexer,
+    telemetry::init_tracing,
 };
 
+mod admin;
+mod config;
 mod db;
+mod inspect;
 mod metrics;
 mod models;
+mod query;
 mod rest;
 mod schema;
+mod snapshot;
 mod sync;
+mod telemetry;
+mod verify;
 
 use tokio_util::sync::CancellationToken;
 
@@ -34,6 +47,12 @@ use tokio_util::sync::CancellationToken;
     about = "An application indexing data on migrated stardust outputs, and serving them through a REST API"
 )]
 struct Cli {
+    /// A TOML configuration file, its values are overridden by the CLI flags
+    /// and the environment variables.
+    #[clap(long, global = true, env = config::CONFIG_FILE_ENV)]
+    config: Option<PathBuf>,
+    #[clap(flatten)]
+    database: DatabaseConfig,
     #[clap(subcommand)]
     command: Command,
 }
@@ -44,76 +63,240 @@ enum Command {
     /// Generate the OpenAPI specification
     GenerateSpec,
     /// Start the Indexer and its REST API
-    StartIndexer {
-        #[clap(long, default_value = "INFO", env = "LOG_LEVEL")]
-        log_level: Level,
-        #[clap(flatten)]
-        connection_pool_config: ConnectionPoolConfig,
-        #[clap(long, default_value = "0.0.0.0:3000", env = "REST_API_SOCKET_ADDRESS")]
-        rest_api_address: std::net::SocketAddr,
-        #[clap(flatten)]
-        indexer_config: Box<IndexerConfig>,
+    StartIndexer(ServiceConfig),
+    /// Print the effective configuration of `start-indexer` as TOML, with
+    /// secrets redacted
+    PrintConfig(ServiceConfig),
+    /// Take a consistent snapshot of the databases, without stopping a running
+    /// Indexer
+    Backup {
+        /// The directory in which the snapshot will be created, the
+        /// `backup_dir` of the configuration file by default.
+        #[clap(long = "target-dir", default_value = "backups", env = "BACKUP_DIR")]
+        backup_dir: PathBuf,
+    },
+    /// Load a snapshot produced by the `backup` command, syncing will resume
+    /// from its checkpoint
+    Restore {
+        /// The snapshot directory to load.
+        #[clap(long)]
+        from: PathBuf,
+        /// Overwrite the databases even if the Indexer already synced.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Print the outputs stored for an address or object ID, without starting
+    /// the Indexer
+    Query(query::QueryArgs),
+    /// Decode a raw object and tell what the Indexer would store for it
+    Inspect(inspect::InspectArgs),
+    /// Check that the objects database is self-consistent
+    Verify {
+        /// Rebuild the derived tables from the stored objects.
+        #[clap(long)]
+        repair: bool,
+    },
+    /// Manage the database migrations
+    Migrations {
+        /// The database to operate on.
+        #[clap(long, value_enum, default_value = "objects", global = true)]
+        database: Name,
+        #[clap(subcommand)]
+        command: MigrationsCommand,
+    },
+}
+
+/// Migration management commands
+#[derive(Subcommand, Clone, Debug)]
+enum MigrationsCommand {
+    /// List the known and applied migrations
+    Status,
+    /// Run the pending migrations
+    Run,
+    /// Revert the applied migrations newer than the given version
+    Revert {
+        /// The version of the latest migration to keep applied.
+        #[clap(long)]
+        to: String,
     },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    dotenv().ok();
+    let cli = parse_cli()?;
+    let database = cli.database;
 
     match cli.command {
         Command::GenerateSpec => {
             generate_openapi_spec();
         }
-        Command::StartIndexer {
-            log_level,
-            connection_pool_config,
-            rest_api_address,
-            indexer_config,
+        Command::StartIndexer(config) => {
+            run_indexer(database, config).await?;
+        }
+        Command::PrintConfig(config) => {
+            if let Some(path) = &cli.config {
+                println!("# Loaded from '{}'", path.display());
+            }
+            let effective = ConfigFile::effective(
+                database.objects_db_url.as_deref(),
+                database.progress_store_db_url.as_deref(),
+                &config,
+            );
+            print!("{}", effective.to_toml()?);
+        }
+        Command::Backup { backup_dir } => {
+            let connection_pool = database.connection_pool(Default::default(), Name::Objects)?;
+            let progress_store_pool =
+                database.connection_pool(Default::default(), Name::ProgressStore)?;
+            let (snapshot_dir, metadata) =
+                snapshot::create_snapshot(&connection_pool, &progress_store_pool, &backup_dir)?;
+            println!(
+                "Snapshot at checkpoint {} written to '{}'",
+                metadata.checkpoint_sequence_number,
+                snapshot_dir.display()
+            );
+        }
+        Command::Restore { from, force } => {
+            let connection_pool = database.connection_pool(Default::default(), Name::Objects)?;
+            let progress_store_pool =
+                database.connection_pool(Default::default(), Name::ProgressStore)?;
+            if !force && snapshot::has_synced(&progress_store_pool).await? {
+                anyhow::bail!("the Indexer already synced, use `--force` to overwrite its data");
+            }
+            let metadata =
+                snapshot::restore_snapshot(&from, &connection_pool, &progress_store_pool).await?;
+            println!(
+                "Snapshot at checkpoint {} restored from '{}'",
+                metadata.checkpoint_sequence_number,
+                from.display()
+            );
+        }
+        Command::Query(args) => {
+            let connection_pool =
+                database.read_only_connection_pool(Default::default(), Name::Objects)?;
+            let outputs = query::query_outputs(&connection_pool, &args)?;
+            println!("{}", query::format_outputs(&outputs, args.format)?);
+        }
+        Command::Inspect(args) => {
+            let report = inspect::inspect(&args)?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::Verify { repair } => {
+            let connection_pool = database.connection_pool(Default::default(), Name::Objects)?;
+            let report = verify::verify_database(&connection_pool, repair)?;
+            for issue in &report.issues {
+                let repairable = if issue.is_repairable() {
+                    " (repairable)"
+                } else {
+                    ""
+                };
+                println!("{issue}{repairable}");
+            }
+            println!(
+                "Checked {} objects: {} issues found, {} repaired",
+                report.objects_checked,
+                report.issues.len(),
+                report.repaired
+            );
+            if report.unresolved() > 0 {
+                anyhow::bail!("{} unresolved issues", report.unresolved());
+            }
+        }
+        Command::Migrations {
+            database: name,
+            command,
         } => {
-            run_indexer(
-                log_level,
-                connection_pool_config,
-                rest_api_address,
-                indexer_config,
-            )
-            .await?;
+            manage_migrations(database.connection_pool(Default::default(), name)?, command)?;
         }
     }
 
     Ok(())
 }
 
+/// Parse the CLI arguments, using the values of the configuration file, if
+/// any, as defaults.
+fn parse_cli() -> anyhow::Result<Cli> {
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let mut command = Cli::command();
+    if let Some(path) = config::config_file_path(&args) {
+        command = ConfigFile::load(&path)?.apply_defaults(command);
+    }
+    Ok(Cli::from_arg_matches(&command.get_matches_from(args))?)
+}
+
 async fn run_indexer(
-    log_level: Level,
-    connection_pool_config: ConnectionPoolConfig,
-    rest_api_address: std::net::SocketAddr,
-    config: Box<IndexerConfig>,
+    database: DatabaseConfig,
+    service_config: ServiceConfig,
 ) -> anyhow::Result<()> {
-    init_tracing(log_level);
-
-    let connection_pool = ConnectionPool::new(connection_pool_config, Name::Objects)?;
+    let effective_config = ConfigFile::effective(
+        database.objects_db_url.as_deref(),
+        database.progress_store_db_url.as_deref(),
+        &service_config,
+    );
+    let ServiceConfig {
+        log_level,
+        tracing_config,
+        connection_pool_config,
+        rest_api_address,
+        rest_config,
+        indexer_config: config,
+    } = service_config;
+
+    // Keep flushing the exported traces until the shutdown
+    let _tracing_guard = init_tracing(log_level, &tracing_config)?;
+
+    // Refuse to start with invalid API keys, rather than failing to serve
+    let api_keys = ApiKeys::new(&rest_config.api_keys)?.map(Arc::new);
+
+    let connection_pool = database.connection_pool(connection_pool_config, Name::Objects)?;
 
     let progress_store_pool =
-        ConnectionPool::new(ConnectionPoolConfig::default(), Name::ProgressStore)?;
+        database.connection_pool(ConnectionPoolConfig::default(), Name::ProgressStore)?;
+
+    // Refuse to run against a database written by a newer binary
+    connection_pool.check_migrations_compatibility()?;
+    progress_store_pool.check_migrations_compatibility()?;
 
     if config.reset_db {
         reset_database(&connection_pool, &progress_store_pool)?;
     }
 
+    if let Some(snapshot_dir) = &config.bootstrap_from {
+        snapshot::bootstrap_from_snapshot(snapshot_dir, &connection_pool, &progress_store_pool)
+            .await?;
+    }
+
     connection_pool.run_migrations()?;
     progress_store_pool.run_migrations()?;
 
+    // Set up the response cache before syncing, so that it gets invalidated
+    if !rest_config.disable_response_cache {
+        RESPONSE_CACHE.get_or_init(|| ResponseCache::new(rest_config.response_cache_size));
+    }
+
     // Spawn synchronization logic from a Fullnode
-    let indexer_handle =
-        Indexer::init(connection_pool.clone(), progress_store_pool, config).await?;
+    let indexer_handle = Indexer::init(
+        connection_pool.clone(),
+        progress_store_pool,
+        config,
+        effective_config,
+    )
+    .await?;
 
     // Set up a CTRL+C handler for graceful shutdown
     let (token, shutdown_handle) = setup_shutdown_signal(indexer_handle);
 
     // Spawn the REST server
-    spawn_rest_server(rest_api_address, connection_pool, token)
-        .await
-        .inspect_err(|e| error!("rest server terminated with error: {e}"))?;
+    spawn_rest_server(
+        rest_api_address,
+        connection_pool,
+        rest_config,
+        api_keys,
+        token,
+    )
+    .await
+    .inspect_err(|e| error!("rest server terminated with error: {e}"))?;
 
     // Wait for the graceful shutdown to complete
     _ = shutdown_handle
@@ -132,6 +315,32 @@ fn reset_database(
     progress_store_pool.revert_all_migrations()
 }
 
+/// Run a migration management command on the database
+fn manage_migrations(pool: ConnectionPool, command: MigrationsCommand) -> anyhow::Result<()> {
+    match command {
+        MigrationsCommand::Status => {}
+        MigrationsCommand::Run => {
+            pool.check_migrations_compatibility()?;
+            pool.run_migrations()?;
+        }
+        MigrationsCommand::Revert { to } => {
+            for version in pool.revert_migrations_to(&to)? {
+                println!("Reverted migration {version}");
+            }
+        }
+    }
+
+    for status in pool.migrations_status()? {
+        let applied = if status.applied { "applied" } else { "pending" };
+        let name = status
+            .name
+            .unwrap_or_else(|| format!("{} (unknown to this binary)", status.version));
+        println!("{applied:<8} {name}");
+    }
+
+    Ok(())
+}
+
 /// Generate and save the OpenAPI specification
 fn generate_openapi_spec() {
     let spec_json = ApiDoc::openapi()
@@ -159,12 +368,6 @@ fn generate_openapi_spec() {
     println!("OpenAPI spec written to '{}'", spec_file.display());
 }
 
-/// Initialize the tracing with custom subscribers
-fn init_tracing(log_level: Level) {
-    let subscriber = FmtSubscriber::builder().with_max_level(log_level).finish();
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
-}
-
 /// Set up a CTRL+C handler for graceful shutdown
 fn setup_shutdown_signal(indexer_handle: Indexer) -> (CancellationToken, JoinHandle<()>) {
     let token = CancellationToken::new();
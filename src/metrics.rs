@@ -3,18 +3,36 @@
 
 use std::{
     net::SocketAddr,
-    sync::{Arc, OnceLock},
+    sync::{Arc, OnceLock, atomic::Ordering},
+    time::Duration,
 };
 
 use axum::{Extension, Router, routing::get};
+use diesel::{
+    dsl::{count_star, sql},
+    prelude::*,
+    sql_types::BigInt,
+};
 use http::StatusCode;
 use prometheus::{
-    IntCounter, IntGauge, Registry, register_int_counter_with_registry,
-    register_int_gauge_with_registry,
+    Histogram, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Registry, register_histogram_vec_with_registry, register_histogram_with_registry,
+    register_int_counter_vec_with_registry, register_int_counter_with_registry,
+    register_int_gauge_vec_with_registry, register_int_gauge_with_registry,
 };
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::{
+    db::ConnectionPool,
+    models::ObjectType,
+    schema::{expiration_unlock_conditions, objects},
+    sync::{LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS, unix_timestamp_ms},
+};
+
+/// Interval between the updates of the metrics read from the database.
+const DATABASE_METRICS_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Metrics for the service.
 #[derive(Clone)]
@@ -23,6 +41,27 @@ pub struct Metrics {
     pub last_checkpoint_indexed: IntGauge,
     pub indexed_basic_outputs_count: IntCounter,
     pub indexed_nft_outputs_count: IntCounter,
+    pub deleted_basic_outputs_count: IntCounter,
+    pub deleted_nft_outputs_count: IntCounter,
+    pub checkpoint_processing_duration_seconds: Histogram,
+    pub inspected_transactions_count: IntCounter,
+    pub matched_transactions_count: IntCounter,
+    pub rejected_objects_count: IntCounterVec,
+    pub checkpoint_failures_count: IntCounter,
+    pub skipped_objects_count: IntCounter,
+    pub sync_halted: IntGauge,
+    pub db_write_duration_seconds: HistogramVec,
+    pub stored_objects: IntGaugeVec,
+    pub stored_expiration_unlock_conditions: IntGauge,
+    pub objects_db_size_bytes: IntGauge,
+    pub checkpoint_timestamp_lag_ms: IntGauge,
+    pub response_cache_hits: IntCounter,
+    pub response_cache_misses: IntCounter,
+    pub rate_limited_requests: IntCounterVec,
+    pub api_key_requests: IntCounterVec,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    pub db_query_duration_seconds: HistogramVec,
 }
 
 impl Metrics {
@@ -52,10 +91,233 @@ impl Metrics {
                 registry,
             )
             .unwrap(),
+            deleted_basic_outputs_count: register_int_counter_with_registry!(
+                "deleted_basic_outputs_count",
+                "The total number of basic outputs deleted",
+                registry,
+            )
+            .unwrap(),
+            deleted_nft_outputs_count: register_int_counter_with_registry!(
+                "deleted_nft_outputs_count",
+                "The total number of NFT outputs deleted",
+                registry,
+            )
+            .unwrap(),
+            checkpoint_processing_duration_seconds: register_histogram_with_registry!(
+                "checkpoint_processing_duration_seconds",
+                "The duration of the processing of a checkpoint",
+                registry,
+            )
+            .unwrap(),
+            inspected_transactions_count: register_int_counter_with_registry!(
+                "inspected_transactions_count",
+                "The total number of checkpoint transactions inspected",
+                registry,
+            )
+            .unwrap(),
+            matched_transactions_count: register_int_counter_with_registry!(
+                "matched_transactions_count",
+                "The total number of checkpoint transactions touching the stardust objects",
+                registry,
+            )
+            .unwrap(),
+            rejected_objects_count: register_int_counter_vec_with_registry!(
+                "rejected_objects_count",
+                "The total number of shared objects not indexed, by reason",
+                &["reason"],
+                registry,
+            )
+            .unwrap(),
+            checkpoint_failures_count: register_int_counter_with_registry!(
+                "checkpoint_failures_count",
+                "The total number of failed attempts to process a checkpoint",
+                registry,
+            )
+            .unwrap(),
+            skipped_objects_count: register_int_counter_with_registry!(
+                "skipped_objects_count",
+                "The total number of objects skipped because they could not be indexed",
+                registry,
+            )
+            .unwrap(),
+            sync_halted: register_int_gauge_with_registry!(
+                "sync_halted",
+                "Whether the syncing is halted on a failing checkpoint",
+                registry,
+            )
+            .unwrap(),
+            db_write_duration_seconds: register_histogram_vec_with_registry!(
+                "db_write_duration_seconds",
+                "The duration of the database writes of the indexer",
+                &["operation"],
+                registry,
+            )
+            .unwrap(),
+            stored_objects: register_int_gauge_vec_with_registry!(
+                "stored_objects",
+                "The current number of stored objects, by type",
+                &["type"],
+                registry,
+            )
+            .unwrap(),
+            stored_expiration_unlock_conditions: register_int_gauge_with_registry!(
+                "stored_expiration_unlock_conditions",
+                "The current number of stored expiration unlock conditions",
+                registry,
+            )
+            .unwrap(),
+            objects_db_size_bytes: register_int_gauge_with_registry!(
+                "objects_db_size_bytes",
+                "The size of the objects database",
+                registry,
+            )
+            .unwrap(),
+            checkpoint_timestamp_lag_ms: register_int_gauge_with_registry!(
+                "checkpoint_timestamp_lag_ms",
+                "The lag of the latest indexed checkpoint timestamp behind the wall clock",
+                registry,
+            )
+            .unwrap(),
+            response_cache_hits: register_int_counter_with_registry!(
+                "response_cache_hits",
+                "The total number of REST queries served from the response cache",
+                registry,
+            )
+            .unwrap(),
+            response_cache_misses: register_int_counter_with_registry!(
+                "response_cache_misses",
+                "The total number of REST queries missing the response cache",
+                registry,
+            )
+            .unwrap(),
+            rate_limited_requests: register_int_counter_vec_with_registry!(
+                "rate_limited_requests",
+                "The total number of REST requests rejected by the rate limiting",
+                &["tier"],
+                registry,
+            )
+            .unwrap(),
+            api_key_requests: register_int_counter_vec_with_registry!(
+                "api_key_requests",
+                "The total number of REST requests authenticated with each API key",
+                &["api_key"],
+                registry,
+            )
+            .unwrap(),
+            http_requests: register_int_counter_vec_with_registry!(
+                "http_requests",
+                "The total number of REST requests by route and status code",
+                &["method", "route", "status"],
+                registry,
+            )
+            .unwrap(),
+            http_request_duration_seconds: register_histogram_vec_with_registry!(
+                "http_request_duration_seconds",
+                "The duration of the REST requests by route",
+                &["method", "route"],
+                registry,
+            )
+            .unwrap(),
+            db_query_duration_seconds: register_histogram_vec_with_registry!(
+                "db_query_duration_seconds",
+                "The duration of the database queries of the REST API",
+                &["query"],
+                registry,
+            )
+            .unwrap(),
         }
     }
 }
 
+/// Update the checkpoint timestamp lag from the wall clock.
+pub(crate) fn update_checkpoint_timestamp_lag(metrics: &Metrics, checkpoint_timestamp_ms: u64) {
+    let lag_ms = unix_timestamp_ms().saturating_sub(checkpoint_timestamp_ms);
+    metrics.checkpoint_timestamp_lag_ms.set(lag_ms as i64);
+}
+
+/// Periodically update the metrics read from the objects database.
+pub(crate) fn spawn_database_metrics_updater(
+    pool: ConnectionPool,
+    cancel_token: CancellationToken,
+    tasks: &mut JoinSet<anyhow::Result<()>>,
+) {
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(DATABASE_METRICS_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => return Ok(()),
+                _ = interval.tick() => {
+                    let Some(metrics) = METRICS.get() else {
+                        continue;
+                    };
+                    let pool = pool.clone();
+                    match tokio::task::spawn_blocking(move || {
+                        update_database_metrics(&pool, metrics)
+                    })
+                    .await
+                    {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => warn!("failed to update the database metrics: {e}"),
+                        Err(e) => warn!("database metrics task failed: {e}"),
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Update the row counts and size of the objects database, and the checkpoint
+/// timestamp lag, which keeps growing while no checkpoint is indexed.
+fn update_database_metrics(pool: &ConnectionPool, metrics: &Metrics) -> anyhow::Result<()> {
+    let mut conn = pool.get_connection()?;
+
+    let counts = objects::table
+        .group_by(objects::object_type)
+        .select((objects::object_type, count_star()))
+        .load::<(ObjectType, i64)>(&mut conn)?;
+    for type_ in [ObjectType::Basic, ObjectType::Nft] {
+        let count = counts
+            .iter()
+            .find_map(|(stored_type, count)| (*stored_type == type_).then_some(*count))
+            .unwrap_or_default();
+        metrics
+            .stored_objects
+            .with_label_values(&[type_.label()])
+            .set(count);
+    }
+
+    metrics.stored_expiration_unlock_conditions.set(
+        expiration_unlock_conditions::table
+            .count()
+            .get_result(&mut conn)?,
+    );
+    metrics.objects_db_size_bytes.set(
+        diesel::select(sql::<BigInt>(
+            "(SELECT page_count FROM pragma_page_count()) * \
+             (SELECT page_size FROM pragma_page_size())",
+        ))
+        .get_result(&mut conn)?,
+    );
+
+    if let Some(timestamp) = LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS.get() {
+        update_checkpoint_timestamp_lag(metrics, timestamp.load(Ordering::SeqCst));
+    }
+
+    Ok(())
+}
+
+/// Start timing a database query, if the metrics are initialized.
+///
+/// The duration is observed when the returned timer is dropped.
+pub(crate) fn start_db_query_timer(query: &str) -> Option<HistogramTimer> {
+    METRICS.get().map(|metrics| {
+        metrics
+            .db_query_duration_seconds
+            .with_label_values(&[query])
+            .start_timer()
+    })
+}
+
 /// Global metrics registry.
 pub(crate) static METRICS: OnceLock<Arc<Metrics>> = OnceLock::new();
 const METRICS_ROUTE: &str = "/metrics";
@@ -111,11 +373,17 @@ pub(crate) async fn metrics(Extension(registry): Extension<Registry>) -> (Status
 
 #[cfg(test)]
 mod tests {
+    use iota_types::base_types::IotaAddress;
     use tracing::Level;
     use tracing_subscriber::FmtSubscriber;
 
     use super::*;
-    use crate::rest::routes::test_utils::get_free_port_for_testing_only;
+    use crate::{
+        db::Name,
+        rest::routes::test_utils::{
+            create_and_insert_basic_output, get_free_port_for_testing_only,
+        },
+    };
 
     #[tokio::test]
     async fn test_metrics() {
@@ -180,4 +448,35 @@ mod tests {
             result.unwrap().unwrap();
         }
     }
+
+    #[test]
+    fn test_database_metrics() -> Result<(), anyhow::Error> {
+        let test_db = "test_database_metrics.db";
+
+        if std::path::Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool = ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects)?;
+        pool.run_migrations()?;
+        let owner = IotaAddress::random_for_testing_only();
+        create_and_insert_basic_output(&mut pool.get_connection()?, owner, 100, 100)?;
+        create_and_insert_basic_output(&mut pool.get_connection()?, owner, 100, 100)?;
+
+        let metrics = Metrics::new(&Registry::default());
+        update_database_metrics(&pool, &metrics)?;
+
+        assert_eq!(
+            metrics.stored_objects.with_label_values(&["basic"]).get(),
+            2
+        );
+        assert_eq!(metrics.stored_objects.with_label_values(&["nft"]).get(), 0);
+        assert_eq!(metrics.stored_expiration_unlock_conditions.get(), 2);
+        assert!(metrics.objects_db_size_bytes.get() > 0);
+
+        // Clean up the test database
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
 }
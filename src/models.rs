@@ -67,19 +67,16 @@ impl TryFrom<iota_types::stardust::output::nft::NftOutput> for ExpirationUnlockC
     }
 }
 
-impl TryFrom<StoredObject> for ExpirationUnlockCondition {
-    type Error = anyhow::Error;
-
-    fn try_from(stored_object: StoredObject) -> Result<Self, Self::Error> {
-        match stored_object.object_type {
-            ObjectType::Basic => Self::try_from(
-                iota_types::stardust::output::basic::BasicOutput::try_from(stored_object)?,
-            ),
-            ObjectType::Nft => Self::try_from(
-                iota_types::stardust::output::nft::NftOutput::try_from(stored_object)?,
-            ),
-        }
-    }
+/// The searchable features of a basic output, which has a row only if it has
+/// a sender or a tag.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::basic_output_features)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct BasicOutputFeatures {
+    pub object_id: IotaAddress,
+    pub sender: Option<IotaAddress>,
+    pub tag: Option<Vec<u8>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Queryable, Selectable, Insertable, AsChangeset)]
@@ -89,6 +86,63 @@ pub struct StoredObject {
     pub id: IotaAddress,
     pub object_type: ObjectType,
     pub contents: Vec<u8>,
+    /// The owner of the address-owned objects, `None` for the shared ones.
+    pub address_owner: Option<IotaAddress>,
+}
+
+impl StoredObject {
+    /// Decode the ID of the output from the contents.
+    pub(crate) fn output_id(&self) -> anyhow::Result<IotaAddress> {
+        Ok(self.decode_output()?.0.into())
+    }
+
+    /// Decode the expiration unlock condition of the output, if it has one.
+    pub(crate) fn expiration_unlock_condition(
+        &self,
+    ) -> anyhow::Result<Option<ExpirationUnlockCondition>> {
+        let (output_id, expiration) = self.decode_output()?;
+        Ok(expiration.map(|expiration| ExpirationUnlockCondition {
+            owner: IotaAddress(expiration.owner),
+            return_address: IotaAddress(expiration.return_address),
+            unix_time: expiration.unix_time as i64,
+            object_id: output_id.into(),
+        }))
+    }
+
+    /// Decode the sender and tag features of a basic output, if it has any.
+    pub(crate) fn basic_output_features(&self) -> anyhow::Result<Option<BasicOutputFeatures>> {
+        if self.object_type != ObjectType::Basic {
+            return Ok(None);
+        }
+        let basic = iota_types::stardust::output::basic::BasicOutput::try_from(self.clone())?;
+        if basic.sender.is_none() && basic.tag.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(BasicOutputFeatures {
+            object_id: (*basic.id.object_id()).into(),
+            sender: basic.sender.map(IotaAddress),
+            tag: basic.tag,
+        }))
+    }
+
+    fn decode_output(
+        &self,
+    ) -> anyhow::Result<(
+        ObjectID,
+        Option<iota_types::stardust::output::unlock_conditions::ExpirationUnlockCondition>,
+    )> {
+        Ok(match self.object_type {
+            ObjectType::Basic => {
+                let basic =
+                    iota_types::stardust::output::basic::BasicOutput::try_from(self.clone())?;
+                (*basic.id.object_id(), basic.expiration)
+            }
+            ObjectType::Nft => {
+                let nft = iota_types::stardust::output::nft::NftOutput::try_from(self.clone())?;
+                (*nft.id.object_id(), nft.expiration)
+            }
+        })
+    }
 }
 
 #[cfg(test)]
@@ -108,6 +162,7 @@ impl StoredObject {
             id: iota_types::base_types::IotaAddress::random_for_testing_only().into(),
             object_type: ObjectType::Nft,
             contents: Default::default(),
+            address_owner: None,
         }
     }
 
@@ -135,26 +190,47 @@ impl StoredObject {
     }
 
     pub(crate) fn new_basic_for_testing(basic: BasicOutput) -> Result<Self, anyhow::Error> {
-        let object = {
-            let move_object = {
-                MoveObject::new_from_execution(
-                    BasicOutput::tag(GAS::type_tag()).into(),
-                    SequenceNumber::default(),
-                    bcs::to_bytes(&basic)?,
-                    &ProtocolConfig::get_for_min_version(),
-                )?
-            };
+        Self::new_basic_with_owner_for_testing(
+            basic,
+            Owner::Shared {
+                initial_shared_version: SequenceNumber::default(),
+            },
+        )
+    }
 
-            Object::new_from_genesis(
-                Data::Move(move_object),
-                Owner::Shared {
-                    initial_shared_version: SequenceNumber::default(),
-                },
-                TransactionDigest::default(),
-            )
-        };
+    pub(crate) fn new_basic_with_owner_for_testing(
+        basic: BasicOutput,
+        owner: Owner,
+    ) -> Result<Self, anyhow::Error> {
+        StoredObject::try_from(Self::basic_object_for_testing(basic, owner)?)
+    }
 
-        StoredObject::try_from(object.clone())
+    /// Wrap the basic output into an object with the given owner.
+    pub(crate) fn basic_object_for_testing(
+        basic: BasicOutput,
+        owner: Owner,
+    ) -> Result<Object, anyhow::Error> {
+        Self::basic_object_with_contents_for_testing(bcs::to_bytes(&basic)?, owner)
+    }
+
+    /// Wrap the contents, which may not be a valid basic output, into a basic
+    /// output object with the given owner.
+    pub(crate) fn basic_object_with_contents_for_testing(
+        contents: Vec<u8>,
+        owner: Owner,
+    ) -> Result<Object, anyhow::Error> {
+        let move_object = MoveObject::new_from_execution(
+            BasicOutput::tag(GAS::type_tag()).into(),
+            SequenceNumber::default(),
+            contents,
+            &ProtocolConfig::get_for_min_version(),
+        )?;
+
+        Ok(Object::new_from_genesis(
+            Data::Move(move_object),
+            owner,
+            TransactionDigest::default(),
+        ))
     }
 }
 
@@ -163,18 +239,21 @@ impl TryFrom<iota_types::object::Object> for StoredObject {
 
     fn try_from(object: iota_types::object::Object) -> anyhow::Result<Self> {
         let object = object.into_inner();
-        if !object.is_shared() {
-            anyhow::bail!("not a shared migrated object");
-        }
+        let address_owner = match object.owner {
+            iota_types::object::Owner::Shared { .. } => None,
+            iota_types::object::Owner::AddressOwner(address) => Some(IotaAddress(address)),
+            _ => anyhow::bail!(Rejection::UnsupportedOwner),
+        };
         let object_type = ObjectType::try_from(&object)?;
         let id = iota_types::base_types::IotaAddress::from(object.id()).into();
         let iota_types::object::Data::Move(move_object) = object.data else {
-            anyhow::bail!("not a move object");
+            anyhow::bail!(Rejection::NotMoveObject);
         };
         Ok(Self {
             id,
             object_type,
             contents: move_object.into_contents(),
+            address_owner,
         })
     }
 }
@@ -202,7 +281,7 @@ impl TryFrom<StoredObject> for iota_types::stardust::output::nft::NftOutput {
 }
 
 #[derive(
-    From, Into, PartialOrd, Ord, Debug, Copy, Clone, PartialEq, Eq, FromSqlRow, AsExpression,
+    From, Into, PartialOrd, Ord, Debug, Copy, Clone, PartialEq, Eq, Hash, FromSqlRow, AsExpression,
 )]
 #[diesel(sql_type = diesel::sql_types::Binary)]
 pub struct IotaAddress(pub iota_types::base_types::IotaAddress);
@@ -232,7 +311,7 @@ impl FromSql<diesel::sql_types::Binary, diesel::sqlite::Sqlite> for IotaAddress
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, TryFromPrimitive, FromSqlRow, Clone, AsExpression)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, TryFromPrimitive, FromSqlRow, Clone, AsExpression)]
 #[diesel(sql_type = diesel::sql_types::Integer)]
 #[repr(u8)]
 pub enum ObjectType {
@@ -240,17 +319,53 @@ pub enum ObjectType {
     Nft,
 }
 
+impl ObjectType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Basic => "basic",
+            Self::Nft => "nft",
+        }
+    }
+}
+
+/// The reasons why an object is not indexed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("neither a shared nor an address-owned object")]
+    UnsupportedOwner,
+    #[error("source object is not a Move object")]
+    NotMoveObject,
+    #[error("not eligible type for indexing")]
+    IneligibleType,
+}
+
+impl Rejection {
+    /// Get the reason of a failed conversion into a [`StoredObject`], if
+    /// the object was rejected.
+    pub fn of(error: &anyhow::Error) -> Option<Self> {
+        error.downcast_ref().copied()
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::UnsupportedOwner => "unsupported_owner",
+            Self::NotMoveObject => "not_move_object",
+            Self::IneligibleType => "ineligible_type",
+        }
+    }
+}
+
 impl TryFrom<&iota_types::object::ObjectInner> for ObjectType {
     type Error = anyhow::Error;
 
     fn try_from(object: &iota_types::object::ObjectInner) -> Result<Self, Self::Error> {
         let Some(struct_tag) = object.struct_tag() else {
-            anyhow::bail!("source object is not a Move object");
+            anyhow::bail!(Rejection::NotMoveObject);
         };
         match (struct_tag.module.as_str(), struct_tag.name.as_str()) {
             ("nft_output", "NftOutput") => Ok(Self::Nft),
             ("basic_output", "BasicOutput") => Ok(Self::Basic),
-            _ => anyhow::bail!("not eligible type for indexing"),
+            _ => anyhow::bail!(Rejection::IneligibleType),
         }
     }
 }
@@ -281,6 +396,32 @@ pub struct LastCheckpointSync {
     pub task_id: String,
 }
 
+/// A failure to process a checkpoint, or one of its objects.
+#[derive(Clone, Debug, PartialEq, Eq, Queryable, Selectable, serde::Serialize)]
+#[diesel(table_name = crate::schema::checkpoint_failures)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct CheckpointFailure {
+    pub id: i32,
+    pub checkpoint_sequence_number: i64,
+    pub transaction_digest: Option<String>,
+    pub object_id: Option<String>,
+    pub error: String,
+    /// What the Indexer did about it, `skipped` or `halted`.
+    pub action: String,
+    pub recorded_at_ms: i64,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = crate::schema::checkpoint_failures)]
+pub struct NewCheckpointFailure {
+    pub checkpoint_sequence_number: i64,
+    pub transaction_digest: Option<String>,
+    pub object_id: Option<String>,
+    pub error: String,
+    pub action: String,
+    pub recorded_at_ms: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -290,6 +431,7 @@ mod tests {
     use super::*;
     use crate::{
         db::{STARDUST_MIGRATIONS, run_migrations},
+        rest::routes::test_utils::create_basic_output,
         schema::objects::dsl::*,
     };
 
@@ -297,7 +439,12 @@ mod tests {
     fn stored_object_round_trip() {
         let data = vec![
             StoredObject::new_dummy_for_testing(),
-            StoredObject::new_dummy_for_testing(),
+            StoredObject {
+                address_owner: Some(
+                    iota_types::base_types::IotaAddress::random_for_testing_only().into(),
+                ),
+                ..StoredObject::new_dummy_for_testing()
+            },
         ];
         let test_db = "stored_object_round_trip.db";
 
@@ -322,4 +469,48 @@ mod tests {
         // clean-up test db
         std::fs::remove_file(test_db).unwrap();
     }
+
+    #[test]
+    fn stored_object_ownership() {
+        let address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = create_basic_output(address, 1, 0);
+
+        let stored_object = StoredObject::new_basic_for_testing(basic.clone()).unwrap();
+        assert_eq!(stored_object.address_owner, None);
+
+        let stored_object = StoredObject::new_basic_with_owner_for_testing(
+            basic.clone(),
+            Owner::AddressOwner(address),
+        )
+        .unwrap();
+        assert_eq!(stored_object.address_owner, Some(IotaAddress(address)));
+
+        let error =
+            StoredObject::new_basic_with_owner_for_testing(basic, Owner::ObjectOwner(address))
+                .unwrap_err();
+        assert_eq!(Rejection::of(&error), Some(Rejection::UnsupportedOwner));
+    }
+
+    #[test]
+    fn stored_object_basic_output_features() {
+        let address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = create_basic_output(address, 1, 0);
+        let stored_object = StoredObject::new_basic_for_testing(basic.clone()).unwrap();
+        assert_eq!(stored_object.basic_output_features().unwrap(), None);
+
+        let stored_object = StoredObject::new_basic_for_testing(BasicOutput {
+            tag: Some(b"deposit".to_vec()),
+            sender: Some(address),
+            ..basic
+        })
+        .unwrap();
+        assert_eq!(
+            stored_object.basic_output_features().unwrap(),
+            Some(BasicOutputFeatures {
+                object_id: stored_object.id,
+                sender: Some(IotaAddress(address)),
+                tag: Some(b"deposit".to_vec()),
+            })
+        );
+    }
 }
@@ -0,0 +1,298 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline queries on the objects database, for operators.
+//!
+//! The outputs are fetched and converted the same way as by the REST API.
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use diesel::prelude::*;
+use iota_types::base_types::ObjectID;
+use serde::Serialize;
+
+use crate::{
+    db::ConnectionPool,
+    models::{IotaAddress, ObjectType, StoredObject},
+    rest::routes::v1::{
+        PaginationParams, load_stored_objects,
+        responses::{BasicOutput, Expiration, NftOutput},
+    },
+    schema::objects,
+};
+
+#[derive(Args, Debug, Clone)]
+pub struct QueryArgs {
+    /// Fetch the outputs whose expiration unlock condition refers to the
+    /// address.
+    #[clap(long, required_unless_present = "object_id")]
+    pub address: Option<iota_types::base_types::IotaAddress>,
+    /// Fetch a single output by its object ID.
+    #[clap(
+        long,
+        conflicts_with_all = ["address", "resolved_at", "include_address_owned", "page", "page_size"]
+    )]
+    pub object_id: Option<ObjectID>,
+    /// The type of the outputs to fetch for the address.
+    #[clap(long, value_enum, default_value = "basic")]
+    pub output_type: OutputType,
+    /// Only fetch the outputs the address can unlock at this unix timestamp in
+    /// milliseconds.
+    #[clap(long)]
+    pub resolved_at: Option<u64>,
+    /// Also fetch the outputs owned by the address.
+    #[clap(long)]
+    pub include_address_owned: bool,
+    /// Page number for pagination.
+    #[clap(long)]
+    pub page: Option<u32>,
+    /// Number of items per page for pagination.
+    #[clap(long)]
+    pub page_size: Option<u32>,
+    #[clap(long, value_enum, default_value = "json")]
+    pub format: OutputFormat,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OutputType {
+    Basic,
+    Nft,
+}
+
+impl From<OutputType> for ObjectType {
+    fn from(output_type: OutputType) -> Self {
+        match output_type {
+            OutputType::Basic => ObjectType::Basic,
+            OutputType::Nft => ObjectType::Nft,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Table,
+}
+
+/// An output as returned by the REST API, tagged with its type.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum QueriedOutput {
+    Basic(BasicOutput),
+    Nft(NftOutput),
+}
+
+impl QueriedOutput {
+    fn id(&self) -> &str {
+        match self {
+            Self::Basic(output) => &output.id,
+            Self::Nft(output) => &output.id,
+        }
+    }
+
+    fn balance(&self) -> u64 {
+        match self {
+            Self::Basic(output) => output.balance.value,
+            Self::Nft(output) => output.balance.value,
+        }
+    }
+
+    fn expiration(&self) -> Option<&Expiration> {
+        match self {
+            Self::Basic(output) => output.expiration.as_ref(),
+            Self::Nft(output) => output.expiration.as_ref(),
+        }
+    }
+}
+
+impl TryFrom<StoredObject> for QueriedOutput {
+    type Error = anyhow::Error;
+
+    fn try_from(stored_object: StoredObject) -> Result<Self, Self::Error> {
+        Ok(match stored_object.object_type {
+            ObjectType::Basic => Self::Basic(
+                iota_types::stardust::output::basic::BasicOutput::try_from(stored_object)?.into(),
+            ),
+            ObjectType::Nft => Self::Nft(
+                iota_types::stardust::output::nft::NftOutput::try_from(stored_object)?.into(),
+            ),
+        })
+    }
+}
+
+/// Fetch the outputs matching the query.
+pub(crate) fn query_outputs(pool: &ConnectionPool, args: &QueryArgs) -> Result<Vec<QueriedOutput>> {
+    let mut conn = pool.get_connection()?;
+
+    let stored_objects = match (args.object_id, args.address) {
+        (Some(object_id), _) => objects::table
+            .find(IotaAddress::from(object_id))
+            .select(StoredObject::as_select())
+            .first::<StoredObject>(&mut conn)
+            .optional()?
+            .into_iter()
+            .collect(),
+        (None, Some(address)) => load_stored_objects(
+            &mut conn,
+            address,
+            &PaginationParams {
+                page: args.page,
+                page_size: args.page_size,
+            },
+            args.output_type.into(),
+            args.resolved_at.map(|timestamp| timestamp as i64),
+            args.include_address_owned,
+        )?,
+        (None, None) => anyhow::bail!("either an address or an object ID must be given"),
+    };
+
+    stored_objects
+        .into_iter()
+        .map(|stored_object| {
+            let id = stored_object.id;
+            QueriedOutput::try_from(stored_object)
+                .with_context(|| format!("failed to decode object {}", id.0))
+        })
+        .collect()
+}
+
+/// Format the outputs for printing.
+pub(crate) fn format_outputs(outputs: &[QueriedOutput], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(outputs)?),
+        OutputFormat::Table => Ok(format_table(outputs)),
+    }
+}
+
+fn format_table(outputs: &[QueriedOutput]) -> String {
+    let mut table = format!(
+        "{:<5} {:<66} {:>20} {:<66} {:<66} {:>10}\n",
+        "TYPE", "ID", "BALANCE", "OWNER", "RETURN ADDRESS", "EXPIRATION"
+    );
+    for output in outputs {
+        let output_type = match output {
+            QueriedOutput::Basic(_) => "basic",
+            QueriedOutput::Nft(_) => "nft",
+        };
+        let (owner, return_address, unix_time) = match output.expiration() {
+            Some(expiration) => (
+                expiration.owner.as_str(),
+                expiration.return_address.as_str(),
+                expiration.unix_time.to_string(),
+            ),
+            None => ("-", "-", "-".to_owned()),
+        };
+        _ = writeln!(
+            table,
+            "{output_type:<5} {:<66} {:>20} {owner:<66} {return_address:<66} {unix_time:>10}",
+            output.id(),
+            output.balance(),
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        db::{DatabaseConfig, Name},
+        rest::routes::test_utils::{create_and_insert_basic_output, create_and_insert_nft_output},
+        schema::expiration_unlock_conditions,
+    };
+
+    fn query(address: Option<iota_types::base_types::IotaAddress>) -> QueryArgs {
+        QueryArgs {
+            address,
+            object_id: None,
+            output_type: OutputType::Basic,
+            resolved_at: None,
+            include_address_owned: false,
+            page: None,
+            page_size: None,
+            format: OutputFormat::Json,
+        }
+    }
+
+    #[test]
+    fn query_outputs_from_read_only_database() {
+        let test_db = "query_outputs_from_read_only_database.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut conn = pool.get_connection().unwrap();
+
+        let owner = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let other = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = create_and_insert_basic_output(&mut conn, owner, 100, 1_000).unwrap();
+        let returned = create_and_insert_basic_output(&mut conn, owner, 200, 1_000).unwrap();
+        let nft = create_and_insert_nft_output(&mut conn, owner, 300, 1_000).unwrap();
+        diesel::update(
+            expiration_unlock_conditions::table.find(IotaAddress::from(*returned.id.object_id())),
+        )
+        .set(expiration_unlock_conditions::return_address.eq(IotaAddress(other)))
+        .execute(&mut conn)
+        .unwrap();
+        drop(conn);
+
+        let database = DatabaseConfig {
+            objects_db_url: Some(test_db.to_owned()),
+            progress_store_db_url: None,
+        };
+        let pool = database
+            .read_only_connection_pool(Default::default(), Name::Objects)
+            .unwrap();
+
+        // Writes are rejected
+        diesel::delete(objects::table)
+            .execute(&mut pool.get_connection().unwrap())
+            .unwrap_err();
+
+        let outputs = query_outputs(&pool, &query(Some(owner))).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs.contains(&QueriedOutput::Basic(basic.clone().into())));
+
+        // After expiration, the output belongs to the return address
+        let resolved = QueryArgs {
+            resolved_at: Some(1_000_000),
+            ..query(Some(owner))
+        };
+        assert_eq!(
+            query_outputs(&pool, &resolved).unwrap(),
+            vec![QueriedOutput::Basic(basic.into())]
+        );
+        let resolved = QueryArgs {
+            resolved_at: Some(1_000_000),
+            ..query(Some(other))
+        };
+        assert_eq!(
+            query_outputs(&pool, &resolved).unwrap(),
+            vec![QueriedOutput::Basic(returned.into())]
+        );
+
+        let by_id = QueryArgs {
+            object_id: Some(*nft.id.object_id()),
+            ..query(None)
+        };
+        let outputs = query_outputs(&pool, &by_id).unwrap();
+        assert_eq!(outputs, vec![QueriedOutput::Nft(nft.clone().into())]);
+
+        let json = format_outputs(&outputs, OutputFormat::Json).unwrap();
+        assert!(json.contains(r#""type": "nft""#));
+        let table = format_outputs(&outputs, OutputFormat::Table).unwrap();
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.contains(&nft.id.object_id().to_string()));
+
+        // clean-up test db
+        drop(pool);
+        std::fs::remove_file(test_db).unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional authentication of the REST API clients with API keys.
+//!
+//! The keys are loaded from a TOML file, which is watched for changes so that
+//! the keys can be rotated without restarting the service:
+//!
+//! ```toml
+//! [tiers.partner]
+//! rate_limit_per_sec = 100
+//! rate_limit_burst = 200
+//! expensive_rate_limit_per_sec = 20
+//! expensive_rate_limit_burst = 40
+//!
+//! [[keys]]
+//! name = "acme"
+//! key = "..."
+//! tier = "partner"
+//! ```
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::HeaderName,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::{ArgAction, Args, builder::BoolishValueParser};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use crate::{
+    metrics::METRICS,
+    rest::{error::ApiError, parse_duration, rate_limit::RateLimits},
+};
+
+pub(crate) const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+/// The probes of the orchestrators, which are always allowed.
+const PROBE_ROUTES: [&str; 2] = ["/live", "/ready"];
+
+/// Settings of the API key authentication.
+#[derive(Args, Debug, Clone)]
+pub struct ApiKeyConfig {
+    /// TOML file defining the API keys and their usage tiers. The API keys are
+    /// disabled if not set.
+    #[arg(long, env = "API_KEYS_FILE")]
+    pub api_keys_file: Option<PathBuf>,
+    /// Interval between the checks for changes of the API keys file, in
+    /// seconds.
+    #[arg(long, value_parser = parse_duration, default_value = "30")]
+    #[arg(env = "API_KEYS_RELOAD_INTERVAL_SECS")]
+    pub api_keys_reload_interval_secs: Duration,
+    /// Reject the requests without a valid API key.
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, require_equals = true)]
+    #[arg(default_value_t = false, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    #[arg(env = "DISABLE_ANONYMOUS_ACCESS")]
+    pub disable_anonymous_access: bool,
+}
+
+impl Default for ApiKeyConfig {
+    fn default() -> Self {
+        Self {
+            api_keys_file: None,
+            api_keys_reload_interval_secs: Duration::from_secs(30),
+            disable_anonymous_access: false,
+        }
+    }
+}
+
+/// The content of the API keys file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKeysFile {
+    #[serde(default)]
+    tiers: HashMap<String, RateLimits>,
+    #[serde(default)]
+    keys: Vec<ApiKeyEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKeyEntry {
+    name: String,
+    key: String,
+    tier: String,
+}
+
+/// An authenticated client.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ApiKey {
+    /// The name of the key, identifying the client in the metrics and logs.
+    pub(crate) name: Arc<str>,
+    pub(crate) limits: RateLimits,
+}
+
+/// The API keys, indexed by their value.
+#[derive(Debug)]
+pub(crate) struct ApiKeys {
+    path: PathBuf,
+    allow_anonymous: bool,
+    inner: RwLock<Loaded>,
+}
+
+#[derive(Debug, Default)]
+struct Loaded {
+    keys: HashMap<String, Arc<ApiKey>>,
+    modified: Option<SystemTime>,
+}
+
+impl ApiKeys {
+    /// Load the API keys file, if configured.
+    pub(crate) fn new(config: &ApiKeyConfig) -> Result<Option<Self>> {
+        let Some(path) = &config.api_keys_file else {
+            anyhow::ensure!(
+                !config.disable_anonymous_access,
+                "anonymous access can only be disabled along with an API keys file"
+            );
+            return Ok(None);
+        };
+
+        let api_keys = Self {
+            path: path.clone(),
+            allow_anonymous: !config.disable_anonymous_access,
+            inner: Default::default(),
+        };
+        api_keys.reload()?;
+        Ok(Some(api_keys))
+    }
+
+    /// Get the client owning the key.
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<ApiKey>> {
+        let inner = self.inner.read().expect("api keys lock poisoned");
+        inner.keys.get(key).cloned()
+    }
+
+    /// Load the keys from the file, keeping the current ones on failure.
+    fn reload(&self) -> Result<()> {
+        let modified = modified_time(&self.path);
+        let keys = load_keys(&self.path)?;
+        info!(
+            "Loaded {} API keys from {}",
+            keys.len(),
+            self.path.display()
+        );
+
+        let mut inner = self.inner.write().expect("api keys lock poisoned");
+        *inner = Loaded { keys, modified };
+        Ok(())
+    }
+
+    fn reload_if_modified(&self) -> Result<()> {
+        let modified = self.inner.read().expect("api keys lock poisoned").modified;
+        if modified_time(&self.path) != modified {
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    /// Reload the keys whenever the file changes, until cancelled.
+    pub(crate) async fn watch(
+        self: Arc<Self>,
+        interval: Duration,
+        cancel_token: CancellationToken,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => return,
+                _ = interval.tick() => {
+                    if let Err(e) = self.reload_if_modified() {
+                        error!("failed to reload the API keys: {e:#}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load_keys(path: &Path) -> Result<HashMap<String, Arc<ApiKey>>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read the API keys file {}", path.display()))?;
+    let file: ApiKeysFile = toml::from_str(&content)
+        .with_context(|| format!("invalid API keys file {}", path.display()))?;
+
+    for (name, limits) in &file.tiers {
+        limits
+            .validate()
+            .with_context(|| format!("invalid tier {name}"))?;
+    }
+
+    let mut names = HashSet::new();
+    let mut keys = HashMap::new();
+    for entry in file.keys {
+        anyhow::ensure!(!entry.key.is_empty(), "empty API key {}", entry.name);
+        anyhow::ensure!(
+            names.insert(entry.name.clone()),
+            "duplicate API key name {}",
+            entry.name
+        );
+        let limits = *file
+            .tiers
+            .get(&entry.tier)
+            .with_context(|| format!("unknown tier {} of API key {}", entry.tier, entry.name))?;
+        let api_key = Arc::new(ApiKey {
+            name: entry.name.as_str().into(),
+            limits,
+        });
+        anyhow::ensure!(
+            keys.insert(entry.key, api_key).is_none(),
+            "API key {} is not unique",
+            entry.name
+        );
+    }
+
+    Ok(keys)
+}
+
+/// Authenticate the clients passing an API key in the `X-API-Key` header,
+/// and reject the anonymous ones if disabled, except for the probes.
+///
+/// The authenticated client is added to the request extensions.
+pub(crate) async fn authenticate(
+    State(api_keys): State<Arc<ApiKeys>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request.headers().get(X_API_KEY) else {
+        if !api_keys.allow_anonymous && !PROBE_ROUTES.contains(&request.uri().path()) {
+            return ApiError::Unauthorized("missing API key".to_owned()).into_response();
+        }
+        return next.run(request).await;
+    };
+
+    let Some(api_key) = key.to_str().ok().and_then(|key| api_keys.get(key)) else {
+        return ApiError::Unauthorized("invalid API key".to_owned()).into_response();
+    };
+
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .api_key_requests
+            .with_label_values(&[&api_key.name])
+            .inc();
+    }
+    request.extensions_mut().insert(api_key);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        db::{ConnectionPool, Name},
+        rest::{
+            RestApiConfig, routes::test_utils::get_free_port_for_testing_only, spawn_rest_server,
+        },
+    };
+
+    const API_KEYS: &str = r#"
+        [tiers.partner]
+        rate_limit_per_sec = 100
+        rate_limit_burst = 200
+        expensive_rate_limit_per_sec = 1
+        expensive_rate_limit_burst = 1
+
+        [[keys]]
+        name = "acme"
+        key = "acme-secret"
+        tier = "partner"
+    "#;
+
+    #[test]
+    fn load_and_reload_api_keys() {
+        let path = Path::new("load_and_reload_api_keys.toml");
+        std::fs::write(path, API_KEYS).unwrap();
+
+        let config = ApiKeyConfig {
+            api_keys_file: Some(path.to_owned()),
+            ..Default::default()
+        };
+        let api_keys = ApiKeys::new(&config).unwrap().unwrap();
+        let api_key = api_keys.get("acme-secret").unwrap();
+        assert_eq!(&*api_key.name, "acme");
+        assert_eq!(api_key.limits.rate_limit_per_sec, 100);
+        assert!(api_keys.get("unknown").is_none());
+
+        // Invalid files are rejected, keeping the loaded keys
+        std::fs::write(
+            path,
+            API_KEYS.replace("tier = \"partner\"", "tier = \"gold\""),
+        )
+        .unwrap();
+        assert!(api_keys.reload().is_err());
+        assert!(api_keys.get("acme-secret").is_some());
+
+        std::fs::write(path, API_KEYS.replace("acme-secret", "acme-rotated")).unwrap();
+        api_keys.reload().unwrap();
+        assert!(api_keys.get("acme-secret").is_none());
+        assert!(api_keys.get("acme-rotated").is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn authenticated_requests() -> Result<(), anyhow::Error> {
+        let test_db = "authenticated_requests.db";
+        let keys_file = Path::new("authenticated_requests.toml");
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+        std::fs::write(keys_file, API_KEYS)?;
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+
+        // Spawn the REST server
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let config = RestApiConfig {
+            api_keys: ApiKeyConfig {
+                api_keys_file: Some(keys_file.to_owned()),
+                disable_anonymous_access: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let api_keys = ApiKeys::new(&config.api_keys)?.map(Arc::new);
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            config,
+            api_keys,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/health", bind_port);
+
+        let resp = client.get(&url).send().await?;
+        assert_eq!(resp.status(), 401);
+
+        let resp = client.get(&url).header(X_API_KEY, "unknown").send().await?;
+        assert_eq!(resp.status(), 401);
+
+        let resp = client
+            .get(&url)
+            .header(X_API_KEY, "acme-secret")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 200);
+
+        // The limits of the key tier apply
+        let resp = client
+            .get(&url)
+            .header(X_API_KEY, "acme-secret")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 429);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        // Clean up the test files
+        std::fs::remove_file(test_db).unwrap();
+        std::fs::remove_file(keys_file).unwrap();
+
+        Ok(())
+    }
+}
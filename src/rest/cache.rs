@@ -0,0 +1,189 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! In-memory cache of the stored objects served for the hottest addresses.
+//!
+//! Entries are invalidated by the `CheckpointWorker` whenever it writes
+//! objects owned by their address, or whose expiration unlock condition refers
+//! to it.
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+};
+
+use hashlink::LruCache;
+use iota_types::base_types::IotaAddress;
+
+use crate::{
+    metrics::METRICS,
+    models::{ObjectType, StoredObject},
+    rest::routes::v1::PaginationParams,
+};
+
+/// Global response cache, only set if enabled.
+pub(crate) static RESPONSE_CACHE: OnceLock<ResponseCache<Vec<StoredObject>>> = OnceLock::new();
+
+/// Identifies a query of stored objects.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    object_type: ObjectType,
+    address: IotaAddress,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    /// The checkpoint timestamp the expiration unlock conditions are resolved
+    /// at, if any.
+    resolved_at_ms: Option<i64>,
+    /// Whether the objects owned by the address are included.
+    include_address_owned: bool,
+}
+
+impl CacheKey {
+    pub(crate) fn new(
+        object_type: ObjectType,
+        address: IotaAddress,
+        pagination: &PaginationParams,
+        resolved_at_ms: Option<i64>,
+        include_address_owned: bool,
+    ) -> Self {
+        Self {
+            object_type,
+            address,
+            page: pagination.page,
+            page_size: pagination.page_size,
+            resolved_at_ms,
+            include_address_owned,
+        }
+    }
+}
+
+/// A bounded LRU cache of query results.
+#[derive(Debug)]
+pub(crate) struct ResponseCache<V> {
+    inner: Mutex<Inner<V>>,
+}
+
+#[derive(Debug)]
+struct Inner<V> {
+    entries: LruCache<CacheKey, V>,
+    /// Incremented on every invalidation, to discard the results of the
+    /// queries that raced with a write.
+    generation: u64,
+}
+
+impl<V: Clone> ResponseCache<V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::new(capacity),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Get the cached result of the query, along with the cache generation to
+    /// pass to [`Self::insert`] on a miss.
+    pub(crate) fn get(&self, key: &CacheKey) -> Result<V, u64> {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        let result = inner.entries.get(key).cloned().ok_or(inner.generation);
+        if let Some(metrics) = METRICS.get() {
+            match result {
+                Ok(_) => metrics.response_cache_hits.inc(),
+                Err(_) => metrics.response_cache_misses.inc(),
+            }
+        }
+        result
+    }
+
+    /// Cache the result of a query, unless the cache was invalidated since
+    /// the given generation.
+    pub(crate) fn insert(&self, key: CacheKey, value: V, generation: u64) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        if inner.generation == generation {
+            inner.entries.insert(key, value);
+        }
+    }
+
+    /// Whether no result is cached.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inner
+            .lock()
+            .expect("cache lock poisoned")
+            .entries
+            .is_empty()
+    }
+
+    /// Drop all the cached results.
+    pub(crate) fn clear(&self) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        inner.generation += 1;
+        inner.entries.clear();
+    }
+
+    /// Drop the cached results of the queries for the addresses.
+    pub(crate) fn invalidate(&self, addresses: &HashSet<IotaAddress>) {
+        let mut inner = self.inner.lock().expect("cache lock poisoned");
+        inner.generation += 1;
+        let stale = inner
+            .entries
+            .iter()
+            .filter(|(key, _)| addresses.contains(&key.address))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in stale {
+            inner.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(address: IotaAddress, page: u32) -> CacheKey {
+        CacheKey::new(
+            ObjectType::Basic,
+            address,
+            &PaginationParams {
+                page: Some(page),
+                page_size: None,
+            },
+            None,
+            false,
+        )
+    }
+
+    #[test]
+    fn cache_eviction_and_invalidation() {
+        let cache = ResponseCache::<u32>::new(2);
+        let address = IotaAddress::random_for_testing_only();
+        let other_address = IotaAddress::random_for_testing_only();
+
+        let generation = cache.get(&key(address, 1)).unwrap_err();
+        cache.insert(key(address, 1), 1, generation);
+        cache.insert(key(other_address, 1), 2, generation);
+        assert_eq!(cache.get(&key(address, 1)), Ok(1));
+
+        // The least recently used entry is evicted
+        cache.insert(key(address, 2), 3, generation);
+        assert!(cache.get(&key(other_address, 1)).is_err());
+        assert_eq!(cache.get(&key(address, 1)), Ok(1));
+
+        // Only the entries of the invalidated address are dropped
+        cache.insert(key(other_address, 1), 2, generation);
+        cache.invalidate(&HashSet::from([address]));
+        assert!(cache.get(&key(address, 1)).is_err());
+        assert_eq!(cache.get(&key(other_address, 1)), Ok(2));
+
+        // Results loaded before an invalidation are discarded
+        cache.insert(key(address, 1), 1, generation);
+        assert!(cache.get(&key(address, 1)).is_err());
+
+        // Clearing drops everything, including the results being loaded
+        let generation = cache.get(&key(address, 1)).unwrap_err();
+        assert!(!cache.is_empty());
+        cache.clear();
+        assert!(cache.is_empty());
+        cache.insert(key(address, 1), 1, generation);
+        assert!(cache.is_empty());
+    }
+}
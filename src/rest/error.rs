@@ -3,7 +3,7 @@
 
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
@@ -14,21 +14,34 @@ use utoipa::ToSchema;
 pub(crate) enum ApiError {
     #[error("bad request: {0}")]
     BadRequest(String),
+    #[error("not found: {0}")]
+    NotFound(String),
     #[error("service unavailable: {0}")]
     ServiceUnavailable(String),
     #[error("internal server error")]
     InternalServerError,
     #[error("forbidden")]
     Forbidden,
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("too many requests, retry after {retry_after_secs} seconds")]
+    TooManyRequests { retry_after_secs: u64 },
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status_code = match self {
             ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
             ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+        };
+        let retry_after_secs = match self {
+            ApiError::TooManyRequests { retry_after_secs } => Some(retry_after_secs),
+            _ => None,
         };
 
         let body = Json(ErrorResponse {
@@ -36,7 +49,13 @@ impl IntoResponse for ApiError {
             error_message: self.to_string(),
         });
 
-        (status_code, body).into_response()
+        let mut response = (status_code, body).into_response();
+        if let Some(retry_after_secs) = retry_after_secs {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after_secs.into());
+        }
+        response
     }
 }
 
@@ -0,0 +1,393 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Middlewares of the REST API.
+use std::{hash::Hasher, sync::atomic::Ordering, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+        header::{ETAG, IF_NONE_MATCH},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::{Instrument, debug, info_span};
+use uuid::Uuid;
+
+use crate::{
+    metrics::METRICS,
+    sync::{LATEST_CHECKPOINT_SEQUENCE_NUMBER, WATERMARK_EPOCH, latest_checkpoint_timestamp_ms},
+};
+
+pub(crate) const X_CHECKPOINT_SEQUENCE: HeaderName =
+    HeaderName::from_static("x-checkpoint-sequence");
+pub(crate) const X_CHECKPOINT_TIMESTAMP: HeaderName =
+    HeaderName::from_static("x-checkpoint-timestamp");
+pub(crate) const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The route label of the requests not matching any route.
+const UNMATCHED_ROUTE: &str = "unmatched";
+/// Longer request ids of the clients are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// The latest indexed checkpoint, which the responses reflect.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Watermark {
+    sequence_number: u64,
+    timestamp_ms: u64,
+    epoch: u64,
+}
+
+impl Watermark {
+    fn latest() -> Option<Self> {
+        Some(Self {
+            timestamp_ms: latest_checkpoint_timestamp_ms()?,
+            sequence_number: LATEST_CHECKPOINT_SEQUENCE_NUMBER
+                .get()?
+                .load(Ordering::SeqCst),
+            epoch: WATERMARK_EPOCH.load(Ordering::SeqCst),
+        })
+    }
+
+    /// The entity tag of the response to the request at this watermark.
+    ///
+    /// The indexed data only changes between checkpoints, so the same request
+    /// gets the same response until the next checkpoint is indexed. The tag
+    /// is stable across restarts and replicas, unless reindexed since.
+    fn etag(&self, method: &Method, uri: &Uri) -> String {
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(&self.sequence_number.to_le_bytes());
+        hasher.write(&self.timestamp_ms.to_le_bytes());
+        if self.epoch != 0 {
+            hasher.write(&self.epoch.to_le_bytes());
+        }
+        hasher.write(method.as_str().as_bytes());
+        hasher.write_u8(0);
+        hasher.write(uri.to_string().as_bytes());
+        format!("W/\"{}-{:016x}\"", self.sequence_number, hasher.finish())
+    }
+
+    fn stamp(&self, headers: &mut HeaderMap, etag: Option<&str>) {
+        headers.insert(X_CHECKPOINT_SEQUENCE, self.sequence_number.into());
+        headers.insert(X_CHECKPOINT_TIMESTAMP, self.timestamp_ms.into());
+        if let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(etag).ok()) {
+            headers.insert(ETAG, etag);
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash function, which unlike the `DefaultHasher` does not
+/// change across Rust releases.
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Stamp the responses with the checkpoint they reflect, and answer
+/// `304 Not Modified` to the conditional requests whose `If-None-Match`
+/// matches the current entity tag.
+pub(crate) async fn checkpoint_stamp(request: Request, next: Next) -> Response {
+    let Some(watermark) = Watermark::latest() else {
+        return next.run(request).await;
+    };
+    let etag = watermark.etag(request.method(), request.uri());
+
+    let cacheable = matches!(*request.method(), Method::GET | Method::HEAD);
+    if cacheable && if_none_match(request.headers(), &etag) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        watermark.stamp(response.headers_mut(), Some(&etag));
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    let cacheable = cacheable && response.status().is_success();
+    watermark.stamp(response.headers_mut(), cacheable.then_some(etag.as_str()));
+    response
+}
+
+/// Run the requests in a tracing span identified by a request id, which is
+/// echoed in the `X-Request-Id` response header, and record their metrics.
+///
+/// The request id set by the client, if any, is kept.
+pub(crate) async fn observe(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .cloned()
+        .unwrap_or_else(|| {
+            Uuid::new_v4()
+                .to_string()
+                .parse()
+                .expect("valid header value")
+        });
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+        .to_owned();
+
+    let span = info_span!(
+        "request",
+        %method,
+        route,
+        request_id = request_id.to_str().unwrap_or_default(),
+    );
+    let started_at = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let elapsed = started_at.elapsed();
+    let status = response.status();
+    span.in_scope(|| {
+        debug!(
+            status = status.as_u16(),
+            elapsed_ms = elapsed.as_millis(),
+            "request completed"
+        )
+    });
+
+    if let Some(metrics) = METRICS.get() {
+        metrics
+            .http_requests
+            .with_label_values(&[method.as_str(), &route, status.as_str()])
+            .inc();
+        metrics
+            .http_request_duration_seconds
+            .with_label_values(&[method.as_str(), &route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    response.headers_mut().insert(X_REQUEST_ID, request_id);
+    response
+}
+
+/// Whether the `If-None-Match` header matches the entity tag, using the weak
+/// comparison.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+    let etag = opaque_tag(etag);
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use iota_types::base_types::ObjectID;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        db::{ConnectionPool, Name},
+        rest::{
+            routes::{
+                test_utils::{create_and_insert_basic_output, get_free_port_for_testing_only},
+                v1::ensure_checkpoint_is_set,
+            },
+            spawn_rest_server,
+        },
+    };
+
+    #[test]
+    fn match_if_none_match_header() {
+        let etag = "W/\"100-0123456789abcdef\"";
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_static(value));
+            headers
+        };
+
+        assert!(if_none_match(&headers("W/\"100-0123456789abcdef\""), etag));
+        assert!(if_none_match(&headers("\"100-0123456789abcdef\""), etag));
+        assert!(if_none_match(
+            &headers("\"99-0123456789abcdef\", W/\"100-0123456789abcdef\""),
+            etag
+        ));
+        assert!(if_none_match(&headers("*"), etag));
+        assert!(!if_none_match(&headers("W/\"99-0123456789abcdef\""), etag));
+        assert!(!if_none_match(&HeaderMap::new(), etag));
+    }
+
+    #[test]
+    fn stable_etag() {
+        let watermark = Watermark {
+            sequence_number: 100,
+            timestamp_ms: 500_000_000,
+            epoch: 0,
+        };
+        let uri = Uri::from_static("/v1/basic/0x1?page=2");
+        assert_eq!(
+            watermark.etag(&Method::GET, &uri),
+            watermark.etag(&Method::GET, &uri)
+        );
+        assert_ne!(
+            watermark.etag(&Method::GET, &uri),
+            watermark.etag(&Method::HEAD, &uri)
+        );
+        let reindexed = Watermark {
+            epoch: 1,
+            ..watermark
+        };
+        assert_ne!(
+            watermark.etag(&Method::GET, &uri),
+            reindexed.etag(&Method::GET, &uri)
+        );
+
+        // Known FNV-1a test vectors
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv1aHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[tokio::test]
+    async fn checkpoint_stamped_responses() -> Result<(), anyhow::Error> {
+        ensure_checkpoint_is_set();
+
+        let test_db = "checkpoint_stamped_responses.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let owner_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        create_and_insert_basic_output(&mut pool.get_connection()?, owner_address, 100, 100)?;
+
+        // Spawn the REST server
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/v1/basic/{}", bind_port, owner_address);
+
+        let resp = client.get(&url).send().await?;
+        assert_eq!(resp.status(), 200);
+        let watermark = Watermark::latest().unwrap();
+        assert_eq!(
+            resp.headers()[X_CHECKPOINT_SEQUENCE],
+            watermark.sequence_number.to_string()
+        );
+        assert_eq!(
+            resp.headers()[X_CHECKPOINT_TIMESTAMP],
+            watermark.timestamp_ms.to_string()
+        );
+        let etag = resp.headers()[ETAG].clone();
+
+        // Revalidation at the same checkpoint
+        let resp = client
+            .get(&url)
+            .header(IF_NONE_MATCH, etag.clone())
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 304);
+        assert_eq!(resp.headers()[ETAG], etag);
+        assert!(resp.bytes().await?.is_empty());
+
+        // Another query has another entity tag
+        let resp = client
+            .get(format!("{url}?page_size=5"))
+            .header(IF_NONE_MATCH, etag.clone())
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 200);
+        assert_ne!(resp.headers()[ETAG], etag);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        // Clean up the test database
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_ids() -> Result<(), anyhow::Error> {
+        let test_db = "request_ids.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+
+        // Spawn the REST server
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/live", bind_port);
+
+        // A request id is generated
+        let resp = client.get(&url).send().await?;
+        assert_eq!(resp.status(), 200);
+        let request_id = resp.headers()[X_REQUEST_ID].to_str()?;
+        assert!(Uuid::parse_str(request_id).is_ok());
+
+        // The request id of the client is echoed, also for unmatched routes
+        let resp = client
+            .get(format!("http://127.0.0.1:{}/unknown", bind_port))
+            .header(X_REQUEST_ID, "client-request-id")
+            .send()
+            .await?;
+        assert_eq!(resp.status(), 403);
+        assert_eq!(resp.headers()[X_REQUEST_ID], "client-request-id");
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        // Clean up the test database
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+}
@@ -1,50 +1,134 @@
 // Copyright (c) 2024 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{Extension, Router, http, response::IntoResponse};
+use axum::{
+    Extension, Router, http,
+    middleware::{from_fn, from_fn_with_state},
+    response::IntoResponse,
+};
+use clap::{ArgAction, Args, builder::BoolishValueParser};
 use http::Method;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use utoipa::OpenApi;
 
 use crate::{
     db::ConnectionPool,
-    rest::{error::ApiError, routes::router_all},
+    rest::{
+        api_keys::authenticate,
+        error::ApiError,
+        rate_limit::{RateLimiter, rate_limit},
+        routes::router_all,
+    },
 };
 
+mod api_keys;
+mod cache;
 mod error;
 mod extractors;
+mod middleware;
+mod rate_limit;
 pub(crate) mod routes;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         routes::health::health,
+        routes::health::ready,
+        routes::health::live,
         routes::v1::basic::basic,
         routes::v1::basic::resolved,
+        routes::v1::basic::by_id,
+        routes::v1::basic::all,
+        routes::v1::basic::by_tag,
+        routes::v1::basic::by_sender,
         routes::v1::nft::nft,
-        routes::v1::nft::resolved
+        routes::v1::nft::resolved,
+        routes::v1::nft::by_id,
+        routes::v1::nft::all,
+        routes::v1::status::status
     ),
+    components(schemas(routes::v1::responses::BytesEncoding)),
     servers((url = "/"))
 )]
 pub struct ApiDoc;
 
+pub use api_keys::ApiKeyConfig;
+pub(crate) use api_keys::ApiKeys;
+pub(crate) use cache::{RESPONSE_CACHE, ResponseCache};
+pub use rate_limit::RateLimitConfig;
+
+/// Settings of the REST API.
+#[derive(Args, Debug, Clone)]
+pub struct RestApiConfig {
+    /// Max time without indexing a checkpoint before `/ready` fails, in
+    /// seconds.
+    #[arg(long, value_parser = parse_duration, default_value = "300")]
+    #[arg(env = "READY_MAX_IDLE_SECS")]
+    pub ready_max_idle_secs: Duration,
+    /// Max lag of the latest indexed checkpoint timestamp behind the wall
+    /// clock before `/ready` fails, in seconds.
+    #[arg(long, value_parser = parse_duration, default_value = "600")]
+    #[arg(env = "READY_MAX_LAG_SECS")]
+    pub ready_max_lag_secs: Duration,
+    /// Max number of query results kept in the in-memory response cache.
+    #[arg(long, default_value_t = 10_000)]
+    #[arg(env = "RESPONSE_CACHE_SIZE")]
+    pub response_cache_size: usize,
+    /// Disable the in-memory response cache.
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, require_equals = true)]
+    #[arg(default_value_t = false, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    #[arg(env = "DISABLE_RESPONSE_CACHE")]
+    pub disable_response_cache: bool,
+    #[clap(flatten)]
+    pub rate_limit: RateLimitConfig,
+    #[clap(flatten)]
+    pub api_keys: ApiKeyConfig,
+}
+
+fn parse_duration(arg: &str) -> Result<Duration, std::num::ParseIntError> {
+    Ok(Duration::from_secs(arg.parse()?))
+}
+
+impl Default for RestApiConfig {
+    fn default() -> Self {
+        Self {
+            ready_max_idle_secs: Duration::from_secs(300),
+            ready_max_lag_secs: Duration::from_secs(600),
+            response_cache_size: 10_000,
+            disable_response_cache: false,
+            rate_limit: Default::default(),
+            api_keys: Default::default(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct State {
     pub(crate) connection_pool: ConnectionPool,
+    pub(crate) config: RestApiConfig,
 }
 
 pub(crate) fn spawn_rest_server(
     socket_addr: SocketAddr,
     connection_pool: ConnectionPool,
+    config: RestApiConfig,
+    api_keys: Option<Arc<ApiKeys>>,
     cancel_token: CancellationToken,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let app = build_app(connection_pool);
+        if let Some(api_keys) = &api_keys {
+            tokio::spawn(api_keys.clone().watch(
+                config.api_keys.api_keys_reload_interval_secs,
+                cancel_token.clone(),
+            ));
+        }
+
+        let app = build_app(connection_pool, config, api_keys);
 
         let listener = tokio::net::TcpListener::bind(socket_addr)
             .await
@@ -52,33 +136,66 @@ pub(crate) fn spawn_rest_server(
 
         info!("Listening on: {}", socket_addr);
 
-        axum::serve(listener, app)
-            .with_graceful_shutdown(async move {
-                cancel_token.cancelled().await;
-                info!("Shutdown signal received.");
-            })
-            .await
-            .inspect_err(|e| error!("server encountered an error: {e}"))
-            .ok();
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            cancel_token.cancelled().await;
+            info!("Shutdown signal received.");
+        })
+        .await
+        .inspect_err(|e| error!("server encountered an error: {e}"))
+        .ok();
     })
 }
 
-fn build_app(connection_pool: ConnectionPool) -> Router {
-    // Allow all origins (CORS policy) - This is safe because the API is public and
-    // does not require authentication. CORS is a browser-enforced mechanism
-    // that restricts cross-origin requests, but since the API is already accessible
-    // without credentials or sensitive data, there is no additional security risk.
-    // Abuse should be mitigated via backend protections such as rate-limiting.
+fn build_app(
+    connection_pool: ConnectionPool,
+    config: RestApiConfig,
+    api_keys: Option<Arc<ApiKeys>>,
+) -> Router {
+    // Allow all origins (CORS policy) - This is safe because the API serves public
+    // data, and its only credentials are the API keys, which grant higher quotas.
+    // CORS is a browser-enforced mechanism that restricts cross-origin requests,
+    // but the keys are sent in the `X-API-Key` header, never attached by the
+    // browser like cookies, so a foreign page can only use a key it already holds.
+    // Abuse is mitigated by the per-client rate limiting below.
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Method::GET)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([
+            http::header::ETAG,
+            http::header::RETRY_AFTER,
+            middleware::X_CHECKPOINT_SEQUENCE,
+            middleware::X_CHECKPOINT_TIMESTAMP,
+            middleware::X_REQUEST_ID,
+        ]);
+
+    let mut router = Router::new().merge(router_all());
+    if !config.rate_limit.disable_rate_limit {
+        if config.rate_limit.trusted_proxies.is_empty() {
+            warn!(
+                "Rate limiting without trusted proxies, the clients behind a reverse proxy or load balancer share the limits of its address"
+            );
+        }
+        let limiter = Arc::new(RateLimiter::new(&config.rate_limit));
+        router = router.layer(from_fn_with_state(limiter, rate_limit));
+    }
+    // Authenticate before rate limiting, to apply the limits of the API keys
+    if let Some(api_keys) = api_keys {
+        router = router.layer(from_fn_with_state(api_keys, authenticate));
+    }
 
-    Router::new()
-        .merge(router_all())
-        .layer(Extension(State { connection_pool }))
+    router
+        .layer(Extension(State {
+            connection_pool,
+            config,
+        }))
         .layer(cors)
         .fallback(fallback)
+        .layer(from_fn(middleware::observe))
 }
 
 async fn fallback() -> impl IntoResponse {
@@ -0,0 +1,452 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-client rate limiting of the REST API, using token buckets.
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::{ArgAction, Args, builder::BoolishValueParser};
+use hashlink::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    metrics::METRICS,
+    rest::{api_keys::ApiKey, error::ApiError},
+};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+/// Above this number of tracked buckets, the least recently used is dropped.
+const MAX_TRACKED_BUCKETS: usize = 100_000;
+
+/// Settings of the rate limiting.
+#[derive(Args, Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Requests per second allowed for each client.
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    #[arg(env = "RATE_LIMIT_PER_SEC")]
+    pub rate_limit_per_sec: u32,
+    /// Max burst of requests allowed for each client.
+    #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u32).range(1..))]
+    #[arg(env = "RATE_LIMIT_BURST")]
+    pub rate_limit_burst: u32,
+    /// Requests per second to the expensive endpoints allowed for each client.
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    #[arg(env = "EXPENSIVE_RATE_LIMIT_PER_SEC")]
+    pub expensive_rate_limit_per_sec: u32,
+    /// Max burst of requests to the expensive endpoints allowed for each
+    /// client.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    #[arg(env = "EXPENSIVE_RATE_LIMIT_BURST")]
+    pub expensive_rate_limit_burst: u32,
+    /// Addresses of the reverse proxies whose `X-Forwarded-For` header is
+    /// trusted to identify the clients.
+    #[arg(long, value_delimiter = ',', env = "TRUSTED_PROXIES")]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Disable the rate limiting.
+    #[arg(long, action = ArgAction::Set, num_args = 0..=1, require_equals = true)]
+    #[arg(default_value_t = false, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    #[arg(env = "DISABLE_RATE_LIMIT")]
+    pub disable_rate_limit: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            rate_limit_per_sec: 20,
+            rate_limit_burst: 40,
+            expensive_rate_limit_per_sec: 5,
+            expensive_rate_limit_burst: 10,
+            trusted_proxies: Vec::new(),
+            disable_rate_limit: false,
+        }
+    }
+}
+
+/// The endpoints sharing a limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Tier {
+    Default,
+    /// The endpoints querying the stored objects.
+    Expensive,
+}
+
+impl Tier {
+    fn of(path: &str) -> Self {
+        if path == "/health" || path.starts_with("/v1/basic/") || path.starts_with("/v1/nft/") {
+            Self::Expensive
+        } else {
+            Self::Default
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Expensive => "expensive",
+        }
+    }
+}
+
+/// The limits applying to a client.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RateLimits {
+    pub(crate) rate_limit_per_sec: u32,
+    pub(crate) rate_limit_burst: u32,
+    pub(crate) expensive_rate_limit_per_sec: u32,
+    pub(crate) expensive_rate_limit_burst: u32,
+}
+
+impl RateLimits {
+    fn limit(&self, tier: Tier) -> Limit {
+        let (per_sec, burst) = match tier {
+            Tier::Default => (self.rate_limit_per_sec, self.rate_limit_burst),
+            Tier::Expensive => (
+                self.expensive_rate_limit_per_sec,
+                self.expensive_rate_limit_burst,
+            ),
+        };
+        Limit {
+            per_sec: per_sec as f64,
+            burst: burst as f64,
+        }
+    }
+
+    /// Check that every request rate is positive.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.rate_limit_per_sec > 0
+                && self.rate_limit_burst > 0
+                && self.expensive_rate_limit_per_sec > 0
+                && self.expensive_rate_limit_burst > 0,
+            "rate limits must be positive"
+        );
+        Ok(())
+    }
+}
+
+impl From<&RateLimitConfig> for RateLimits {
+    fn from(config: &RateLimitConfig) -> Self {
+        Self {
+            rate_limit_per_sec: config.rate_limit_per_sec,
+            rate_limit_burst: config.rate_limit_burst,
+            expensive_rate_limit_per_sec: config.expensive_rate_limit_per_sec,
+            expensive_rate_limit_burst: config.expensive_rate_limit_burst,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Limit {
+    per_sec: f64,
+    burst: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_sec).min(limit.burst);
+        self.updated_at = now;
+    }
+
+    /// Take a token, or get the time until one is available.
+    fn take(&mut self, limit: Limit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / limit.per_sec))
+        }
+    }
+}
+
+/// Identifies the owner of a bucket.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Client {
+    Anonymous(IpAddr),
+    /// The name of an API key.
+    ApiKey(Arc<str>),
+}
+
+/// Token buckets of the clients.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// The limits of the anonymous clients.
+    limits: RateLimits,
+    trusted_proxies: Vec<IpAddr>,
+    buckets: Mutex<LruCache<(Client, Tier), Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            limits: config.into(),
+            trusted_proxies: config.trusted_proxies.clone(),
+            buckets: Mutex::new(LruCache::new(MAX_TRACKED_BUCKETS)),
+        }
+    }
+
+    fn check(
+        &self,
+        client: Client,
+        tier: Tier,
+        limits: &RateLimits,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let limit = limits.limit(tier);
+        let key = (client, tier);
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        let mut bucket = buckets
+            .get(&key)
+            .copied()
+            .unwrap_or_else(|| Bucket::full(limit, now));
+        let result = bucket.take(limit, now);
+        // Marks the bucket as the most recently used, the least recently used
+        // being dropped when tracking too many
+        buckets.insert(key, bucket);
+        result
+    }
+
+    /// Identify the client, from the `X-Forwarded-For` header if the peer is
+    /// a trusted proxy.
+    ///
+    /// The header is read from right to left, skipping the trusted proxies,
+    /// as the leftmost addresses can be forged by the client. The entries
+    /// which are not addresses, like `unknown`, are skipped, and the ports
+    /// dropped.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+        let forwarded = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|address| parse_forwarded_address(address.trim()))
+            .collect::<Vec<_>>();
+
+        forwarded
+            .iter()
+            .rev()
+            .find(|address| !self.trusted_proxies.contains(address))
+            .or(forwarded.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+}
+
+/// Parse an entry of the `X-Forwarded-For` header, with or without a port.
+fn parse_forwarded_address(address: &str) -> Option<IpAddr> {
+    address
+        .parse::<IpAddr>()
+        .or_else(|_| address.parse::<SocketAddr>().map(|address| address.ip()))
+        .ok()
+}
+
+/// Reject the requests of the clients exceeding their limit with
+/// `429 Too Many Requests`.
+///
+/// The clients authenticated with an API key share the limits of the key,
+/// the anonymous ones are identified by their IP address.
+pub(crate) async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let tier = Tier::of(request.uri().path());
+    let (client, limits) = match request.extensions().get::<Arc<ApiKey>>() {
+        Some(api_key) => (Client::ApiKey(api_key.name.clone()), api_key.limits),
+        None => {
+            let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>()
+            else {
+                return next.run(request).await;
+            };
+            let client_ip = limiter.client_ip(peer.ip(), request.headers());
+            (Client::Anonymous(client_ip), limiter.limits)
+        }
+    };
+
+    if let Err(retry_after) = limiter.check(client, tier, &limits, Instant::now()) {
+        if let Some(metrics) = METRICS.get() {
+            metrics
+                .rate_limited_requests
+                .with_label_values(&[tier.label()])
+                .inc();
+        }
+        return ApiError::TooManyRequests {
+            retry_after_secs: retry_after.as_secs_f64().ceil().max(1.0) as u64,
+        }
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use axum::http::{HeaderValue, header::RETRY_AFTER};
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        db::{ConnectionPool, Name},
+        rest::{
+            RestApiConfig, routes::test_utils::get_free_port_for_testing_only, spawn_rest_server,
+        },
+    };
+
+    #[test]
+    fn token_bucket_and_client_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            rate_limit_per_sec: 1,
+            rate_limit_burst: 2,
+            trusted_proxies: vec![proxy],
+            ..Default::default()
+        });
+        let limits = limiter.limits;
+        let check = |client: IpAddr, tier, now| {
+            limiter.check(Client::Anonymous(client), tier, &limits, now)
+        };
+
+        let now = Instant::now();
+        assert!(check(client, Tier::Default, now).is_ok());
+        assert!(check(client, Tier::Default, now).is_ok());
+        let retry_after = check(client, Tier::Default, now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+        // The other clients and tiers have their own buckets
+        assert!(check(proxy, Tier::Default, now).is_ok());
+        assert!(check(client, Tier::Expensive, now).is_ok());
+        // Tokens are refilled over time
+        let later = now + Duration::from_millis(1500);
+        assert!(check(client, Tier::Default, later).is_ok());
+        assert!(check(client, Tier::Default, later).is_err());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.7, 192.0.2.1, 10.0.0.1"),
+        );
+        assert_eq!(limiter.client_ip(proxy, &headers), client);
+        // The entries which are not addresses are skipped
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("unknown, 192.0.2.1:4711, 10.0.0.1"),
+        );
+        assert_eq!(limiter.client_ip(proxy, &headers), client);
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("[2001:db8::1]:443, unknown"),
+        );
+        assert_eq!(
+            limiter.client_ip(proxy, &headers),
+            "2001:db8::1".parse::<IpAddr>().unwrap()
+        );
+        // The header is ignored from untrusted peers
+        assert_eq!(limiter.client_ip(client, &headers), client);
+        assert_eq!(limiter.client_ip(proxy, &HeaderMap::new()), proxy);
+    }
+
+    #[test]
+    fn drop_least_recently_used_buckets() {
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let limiter = RateLimiter {
+            buckets: Mutex::new(LruCache::new(2)),
+            ..RateLimiter::new(&RateLimitConfig {
+                rate_limit_burst: 1,
+                ..Default::default()
+            })
+        };
+        let limits = limiter.limits;
+        let now = Instant::now();
+        let check =
+            |client: IpAddr| limiter.check(Client::Anonymous(client), Tier::Default, &limits, now);
+        assert!(check(client).is_ok());
+        assert!(check(other).is_ok());
+        assert!(check(client).is_err());
+        assert!(check("192.0.2.3".parse().unwrap()).is_ok());
+        // The bucket of the other client was dropped, as the least recently used
+        assert!(check(client).is_err());
+        assert!(check(other).is_ok());
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests() -> Result<(), anyhow::Error> {
+        let test_db = "rate_limited_requests.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+
+        // Spawn the REST server
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            RestApiConfig {
+                rate_limit: RateLimitConfig {
+                    expensive_rate_limit_per_sec: 1,
+                    expensive_rate_limit_burst: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/health", bind_port)).await?;
+        assert_eq!(resp.status(), 200);
+
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/health", bind_port)).await?;
+        assert_eq!(resp.status(), 429);
+        assert_eq!(resp.headers()[RETRY_AFTER], "1");
+
+        // The cheap endpoints are still served
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/live", bind_port)).await?;
+        assert_eq!(resp.status(), 200);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        // Clean up the test database
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
 // Copyright (c) 2024 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use axum::Extension;
+use std::{sync::atomic::Ordering, time::Duration};
+
+use axum::{
+    Extension, Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use tracing::error;
@@ -9,9 +15,14 @@ use utoipa::ToSchema;
 
 use crate::{
     impl_into_response,
+    metrics::start_db_query_timer,
     models::ObjectType,
-    rest::{ApiError, State},
+    rest::{ApiError, RestApiConfig, State},
     schema::objects::{dsl::objects, object_type},
+    sync::{
+        INDEXER_RUNNING, LAST_PROGRESS_UNIX_TIMESTAMP_MS, latest_checkpoint_timestamp_ms,
+        unix_timestamp_ms,
+    },
 };
 
 /// Retrieve the health of the service.
@@ -22,15 +33,19 @@ use crate::{
     responses(
         (status = 200, description = "Successful request", body = HealthResponse),
         (status = 503, description = "Service unavailable"),
-        (status = 500, description = "Internal server error")
+        (status = 500, description = "Internal server error"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
     ),
 )]
+#[tracing::instrument(skip_all, err)]
 pub(crate) async fn health(Extension(state): Extension<State>) -> Result<HealthResponse, ApiError> {
     let mut conn = state.connection_pool.get_connection().map_err(|e| {
         error!("failed to get connection: {e}");
         ApiError::ServiceUnavailable(format!("failed to get connection: {e}"))
     })?;
 
+    let _timer = start_db_query_timer("health_counts");
     let objects_count = objects.count().get_result(&mut conn).map_err(|e| {
         error!("failed to count objects: {e}");
         ApiError::ServiceUnavailable(format!("failed to count objects: {e}"))
@@ -69,6 +84,101 @@ pub(crate) struct HealthResponse {
 }
 impl_into_response!(HealthResponse);
 
+/// Check whether the service is able to serve fresh data.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    description = "Check whether the service is able to serve fresh data. It fails if the syncing task exited, if no checkpoint was indexed recently, or if the latest indexed checkpoint lags behind the wall clock.",
+    responses(
+        (status = 200, description = "The service is ready", body = ReadinessResponse),
+        (status = 503, description = "The service is not ready", body = ReadinessResponse)
+    ),
+)]
+pub(crate) async fn ready(Extension(state): Extension<State>) -> ReadinessResponse {
+    check_readiness(
+        &state.config,
+        unix_timestamp_ms(),
+        INDEXER_RUNNING.load(Ordering::SeqCst),
+        LAST_PROGRESS_UNIX_TIMESTAMP_MS.load(Ordering::SeqCst),
+        latest_checkpoint_timestamp_ms(),
+    )
+}
+
+/// Check whether the service is up, without touching the database.
+#[utoipa::path(
+    get,
+    path = "/live",
+    description = "Check whether the service is up, without touching the database.",
+    responses(
+        (status = 200, description = "The service is up")
+    ),
+)]
+pub(crate) async fn live() -> StatusCode {
+    StatusCode::OK
+}
+
+fn check_readiness(
+    config: &RestApiConfig,
+    now_ms: u64,
+    indexer_running: bool,
+    last_progress_ms: u64,
+    latest_checkpoint_ms: Option<u64>,
+) -> ReadinessResponse {
+    let mut failures = Vec::new();
+
+    if !indexer_running {
+        failures.push("the syncing task is not running".to_owned());
+    }
+
+    let idle = (last_progress_ms > 0)
+        .then(|| Duration::from_millis(now_ms.saturating_sub(last_progress_ms)));
+    if let Some(idle) = idle.filter(|idle| *idle > config.ready_max_idle_secs) {
+        failures.push(format!("no checkpoint indexed for {}s", idle.as_secs()));
+    }
+
+    let lag = latest_checkpoint_ms
+        .map(|timestamp| Duration::from_millis(now_ms.saturating_sub(timestamp)));
+    match lag {
+        None => failures.push("no checkpoint indexed yet".to_owned()),
+        Some(lag) if lag > config.ready_max_lag_secs => failures.push(format!(
+            "the latest indexed checkpoint is {}s behind",
+            lag.as_secs()
+        )),
+        Some(_) => {}
+    }
+
+    ReadinessResponse {
+        ready: failures.is_empty(),
+        indexer_running,
+        idle_secs: idle.map(|idle| idle.as_secs()),
+        lag_secs: lag.map(|lag| lag.as_secs()),
+        failures,
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ReadinessResponse {
+    pub ready: bool,
+    pub indexer_running: bool,
+    /// Seconds since a checkpoint was last indexed.
+    pub idle_secs: Option<u64>,
+    /// Seconds between the latest indexed checkpoint timestamp and now.
+    pub lag_secs: Option<u64>,
+    /// The reasons why the service is not ready.
+    pub failures: Vec<String>,
+}
+
+impl IntoResponse for ReadinessResponse {
+    fn into_response(self) -> Response {
+        let status_code = if self.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status_code, Json(self)).into_response()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, path::Path};
@@ -78,6 +188,7 @@ mod tests {
     use tracing::Level;
     use tracing_subscriber::FmtSubscriber;
 
+    use super::*;
     use crate::{
         db::{ConnectionPool, Name},
         rest::{
@@ -144,6 +255,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{}", bind_port).parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
@@ -158,6 +271,16 @@ mod tests {
         assert_eq!(health_response.basic_objects_count, 3);
         assert_eq!(health_response.nft_objects_count, 2);
 
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/live", bind_port)).await?;
+        assert_eq!(resp.status(), 200);
+
+        // No syncing task runs in the tests
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/ready", bind_port)).await?;
+        assert_eq!(resp.status(), 503);
+        let readiness_response: ReadinessResponse = resp.json().await?;
+        assert!(!readiness_response.ready);
+        assert!(!readiness_response.indexer_running);
+
         cancel_token.cancel();
         handle.await.unwrap();
 
@@ -166,4 +289,43 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn readiness_checks() {
+        let config = RestApiConfig {
+            ready_max_idle_secs: Duration::from_secs(60),
+            ready_max_lag_secs: Duration::from_secs(120),
+            ..Default::default()
+        };
+        let now_ms = 1_000_000_000;
+
+        let readiness =
+            check_readiness(&config, now_ms, true, now_ms - 1_000, Some(now_ms - 5_000));
+        assert!(readiness.ready);
+        assert_eq!(readiness.idle_secs, Some(1));
+        assert_eq!(readiness.lag_secs, Some(5));
+
+        // The syncing task exited
+        let readiness = check_readiness(&config, now_ms, false, now_ms - 1_000, Some(now_ms));
+        assert!(!readiness.ready);
+        assert_eq!(readiness.failures.len(), 1);
+
+        // No checkpoint indexed for too long
+        let readiness = check_readiness(&config, now_ms, true, now_ms - 61_000, Some(now_ms));
+        assert!(!readiness.ready);
+        assert_eq!(readiness.failures, vec!["no checkpoint indexed for 61s"]);
+
+        // Still catching up with the chain
+        let readiness = check_readiness(&config, now_ms, true, now_ms, Some(now_ms - 121_000));
+        assert!(!readiness.ready);
+        assert_eq!(
+            readiness.failures,
+            vec!["the latest indexed checkpoint is 121s behind"]
+        );
+
+        // Nothing synced yet
+        let readiness = check_readiness(&config, now_ms, true, now_ms, None);
+        assert!(!readiness.ready);
+        assert_eq!(readiness.lag_secs, None);
+    }
 }
@@ -5,7 +5,10 @@ use axum::{Router, routing::get};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::rest::{ApiDoc, routes::health::health};
+use crate::rest::{
+    ApiDoc,
+    routes::health::{health, live, ready},
+};
 
 pub(crate) mod health;
 pub(crate) mod v1;
@@ -14,6 +17,8 @@ pub(crate) fn router_all() -> Router {
     Router::new().merge(v1::router()).merge(
         Router::new()
             .route("/health", get(health))
+            .route("/ready", get(ready))
+            .route("/live", get(live))
             .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi())),
     )
 }
@@ -43,16 +48,14 @@ pub(crate) mod test_utils {
         }
     }
 
-    /// Create and insert a basic output into the database.
-    pub(crate) fn create_and_insert_basic_output(
-        connection: &mut PoolConnection,
+    /// Create a basic output with an expiration unlock condition.
+    pub(crate) fn create_basic_output(
         owner_address: iota_types::base_types::IotaAddress,
         balance: u64,
         unix_time: u32,
-    ) -> Result<iota_types::stardust::output::basic::BasicOutput, anyhow::Error> {
-        let basic_object_id = ObjectID::random();
-        let basic_output = iota_types::stardust::output::basic::BasicOutput {
-            id: UID::new(basic_object_id),
+    ) -> iota_types::stardust::output::basic::BasicOutput {
+        iota_types::stardust::output::basic::BasicOutput {
+            id: UID::new(ObjectID::random()),
             balance: Balance::new(balance),
             native_tokens: Bag::default(),
             storage_deposit_return: None,
@@ -67,8 +70,18 @@ pub(crate) mod test_utils {
             metadata: None,
             tag: None,
             sender: None,
-        };
+        }
+    }
 
+    /// Create and insert a basic output into the database.
+    pub(crate) fn create_and_insert_basic_output(
+        connection: &mut PoolConnection,
+        owner_address: iota_types::base_types::IotaAddress,
+        balance: u64,
+        unix_time: u32,
+    ) -> Result<iota_types::stardust::output::basic::BasicOutput, anyhow::Error> {
+        let basic_output = create_basic_output(owner_address, balance, unix_time);
+        let basic_object_id = *basic_output.id.object_id();
         let stored_object = StoredObject::new_basic_for_testing(basic_output.clone())?;
 
         insert_into(objects)
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use axum::{Extension, Router, extract::Query, routing::get};
+use serde::Deserialize;
 use tracing::error;
 
 use crate::{
@@ -11,8 +12,12 @@ use crate::{
         error::ApiError,
         extractors::Path,
         routes::v1::{
-            PaginationParams, fetch_stored_objects,
-            responses::{BasicOutput, BasicOutputVec},
+            EncodingParams, FeatureFilter, PaginationParams, fetch_basic_objects_by_feature,
+            fetch_stored_object, fetch_stored_objects,
+            responses::{
+                BasicOutput, BasicOutputVec, BasicOutputWithOwnership, BasicOutputWithOwnershipVec,
+                Ownership,
+            },
         },
     },
 };
@@ -21,6 +26,15 @@ pub(crate) fn router() -> Router {
     Router::new()
         .route("/basic/:address", get(basic))
         .route("/basic/resolved/:address", get(resolved))
+        .route("/basic/by-id/:object_id", get(by_id))
+        .route("/basic/all/:address", get(all))
+        .route("/basic/by-tag/:tag", get(by_tag))
+        .route("/basic/by-sender/:address", get(by_sender))
+}
+
+#[derive(Deserialize, Default)]
+struct TagSearchParams {
+    prefix: Option<bool>,
 }
 
 /// Get the `BasicOutput`s owned by the address
@@ -36,22 +50,26 @@ description =
         (status = 400, description = "Bad request"),
         (status = 500, description = "Internal server error"),
         (status = 503, description = "Service unavailable"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
     ),
     params(
         ("address" = String, Path, description = "The hexadecimal address for which to fetch basic outputs."),
         ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
-        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10.")
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10."),
+        EncodingParams
     )
 )]
 async fn basic(
     Path(address): Path<iota_types::base_types::IotaAddress>,
     Query(pagination): Query<PaginationParams>,
+    Query(encoding): Query<EncodingParams>,
     Extension(state): Extension<State>,
 ) -> Result<BasicOutputVec, ApiError> {
     let stored_objects =
-        fetch_stored_objects(address, pagination, state, ObjectType::Basic, false)?;
-    let basic_outputs = stored_objects_to_basic_outputs(stored_objects)?;
+        fetch_stored_objects(address, pagination, state, ObjectType::Basic, false, false)?;
+    let basic_outputs = stored_objects_to_basic_outputs(stored_objects, &encoding)?;
     Ok(BasicOutputVec(basic_outputs))
 }
 
@@ -78,58 +96,252 @@ description =
         (status = 400, description = "Bad request"),
         (status = 500, description = "Internal server error"),
         (status = 503, description = "Service unavailable"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
     ),
     params(
         ("address" = String, Path, description = "The hexadecimal address for which to fetch basic outputs."),
         ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
-        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10.")
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10."),
+        EncodingParams
     )
 )]
 async fn resolved(
     Path(address): Path<iota_types::base_types::IotaAddress>,
     Query(pagination): Query<PaginationParams>,
+    Query(encoding): Query<EncodingParams>,
     Extension(state): Extension<State>,
 ) -> Result<BasicOutputVec, ApiError> {
-    let stored_objects = fetch_stored_objects(address, pagination, state, ObjectType::Basic, true)?;
-    let basic_outputs = stored_objects_to_basic_outputs(stored_objects)?;
+    let stored_objects =
+        fetch_stored_objects(address, pagination, state, ObjectType::Basic, true, false)?;
+    let basic_outputs = stored_objects_to_basic_outputs(stored_objects, &encoding)?;
     Ok(BasicOutputVec(basic_outputs))
 }
 
+/// Get the `BasicOutput`s owned by the address, or whose expiration unlock
+/// condition refers to it.
+#[utoipa::path(
+get,
+path = "/v1/basic/all/{address}",
+description =
+    "Fetches all the basic outputs of a specified address with optional pagination.
+    It returns the basic outputs owned by the address, followed by the shared ones with expiration unlock conditions
+    that refer to the address either as the `owner` or as the `return_address`, each ordered by object ID. The `ownership`
+    field tells them apart. Results can be paginated by providing optional `page` and `page_size` query parameters.",
+    responses(
+        (status = 200, description = "Successful request", body = BasicOutputWithOwnershipVec),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("address" = String, Path, description = "The hexadecimal address for which to fetch basic outputs."),
+        ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10."),
+        EncodingParams
+    )
+)]
+async fn all(
+    Path(address): Path<iota_types::base_types::IotaAddress>,
+    Query(pagination): Query<PaginationParams>,
+    Query(encoding): Query<EncodingParams>,
+    Extension(state): Extension<State>,
+) -> Result<BasicOutputWithOwnershipVec, ApiError> {
+    let stored_objects =
+        fetch_stored_objects(address, pagination, state, ObjectType::Basic, false, true)?;
+    Ok(BasicOutputWithOwnershipVec(
+        stored_objects_to_basic_outputs_with_ownership(stored_objects, &encoding)?,
+    ))
+}
+
+/// Get a `BasicOutput` by its object ID
+#[utoipa::path(
+get,
+path = "/v1/basic/by-id/{object_id}",
+description =
+    "Fetches a basic output by its object ID.
+    Unlike the queries by address, it also returns the outputs without expiration unlock condition.",
+    responses(
+        (status = 200, description = "Successful request", body = BasicOutput),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Not found"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("object_id" = String, Path, description = "The hexadecimal ID of the basic output."),
+        EncodingParams
+    )
+)]
+async fn by_id(
+    Path(object_id): Path<iota_types::base_types::ObjectID>,
+    Query(encoding): Query<EncodingParams>,
+    Extension(state): Extension<State>,
+) -> Result<BasicOutput, ApiError> {
+    let stored_object = fetch_stored_object(object_id, state, ObjectType::Basic)?;
+    stored_object_to_basic_output(stored_object, &encoding)
+}
+
+/// Get the `BasicOutput`s with the given tag
+#[utoipa::path(
+get,
+path = "/v1/basic/by-tag/{tag}",
+description =
+    "Fetches the basic outputs whose tag feature matches the given tag, whether they are shared or address-owned.
+    The tag is given as `0x`-prefixed hex, or as UTF-8 text otherwise. With `prefix=true`, the outputs whose tag
+    starts with it are returned, ordered by tag. Results can be paginated by providing optional `page` and
+    `page_size` query parameters.",
+    responses(
+        (status = 200, description = "Successful request", body = BasicOutputWithOwnershipVec),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("tag" = String, Path, description = "The tag to search for, `0x`-prefixed hex or UTF-8 text."),
+        ("prefix" = Option<bool>, Query, description = "Whether to match the tags starting with the given one. Defaults to false."),
+        ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10."),
+        EncodingParams
+    )
+)]
+async fn by_tag(
+    Path(tag): Path<String>,
+    Query(search): Query<TagSearchParams>,
+    Query(pagination): Query<PaginationParams>,
+    Query(encoding): Query<EncodingParams>,
+    Extension(state): Extension<State>,
+) -> Result<BasicOutputWithOwnershipVec, ApiError> {
+    let tag = parse_tag(&tag)?;
+    let filter = if search.prefix.unwrap_or_default() {
+        FeatureFilter::TagPrefix(tag)
+    } else {
+        FeatureFilter::Tag(tag)
+    };
+    let stored_objects = fetch_basic_objects_by_feature(filter, pagination, state)?;
+    Ok(BasicOutputWithOwnershipVec(
+        stored_objects_to_basic_outputs_with_ownership(stored_objects, &encoding)?,
+    ))
+}
+
+/// Get the `BasicOutput`s sent by the address
+#[utoipa::path(
+get,
+path = "/v1/basic/by-sender/{address}",
+description =
+    "Fetches the basic outputs whose sender feature is the given address, whether they are shared or address-owned.
+    Results can be paginated by providing optional `page` and `page_size` query parameters.",
+    responses(
+        (status = 200, description = "Successful request", body = BasicOutputWithOwnershipVec),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("address" = String, Path, description = "The hexadecimal address of the sender."),
+        ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10."),
+        EncodingParams
+    )
+)]
+async fn by_sender(
+    Path(address): Path<iota_types::base_types::IotaAddress>,
+    Query(pagination): Query<PaginationParams>,
+    Query(encoding): Query<EncodingParams>,
+    Extension(state): Extension<State>,
+) -> Result<BasicOutputWithOwnershipVec, ApiError> {
+    let stored_objects =
+        fetch_basic_objects_by_feature(FeatureFilter::Sender(address), pagination, state)?;
+    Ok(BasicOutputWithOwnershipVec(
+        stored_objects_to_basic_outputs_with_ownership(stored_objects, &encoding)?,
+    ))
+}
+
+/// Parse a tag given as `0x`-prefixed hex, or as UTF-8 text otherwise.
+fn parse_tag(tag: &str) -> Result<Vec<u8>, ApiError> {
+    match tag.strip_prefix("0x") {
+        Some(hex_tag) => hex::decode(hex_tag)
+            .map_err(|e| ApiError::BadRequest(format!("invalid hex tag {tag}: {e}"))),
+        None => Ok(tag.as_bytes().to_vec()),
+    }
+}
+
 fn stored_objects_to_basic_outputs(
     stored_objects: Vec<StoredObject>,
+    encoding: &EncodingParams,
 ) -> Result<Vec<BasicOutput>, ApiError> {
+    stored_objects
+        .into_iter()
+        .map(|stored_object| stored_object_to_basic_output(stored_object, encoding))
+        .collect()
+}
+
+fn stored_objects_to_basic_outputs_with_ownership(
+    stored_objects: Vec<StoredObject>,
+    encoding: &EncodingParams,
+) -> Result<Vec<BasicOutputWithOwnership>, ApiError> {
     stored_objects
         .into_iter()
         .map(|stored_object| {
-            iota_types::stardust::output::basic::BasicOutput::try_from(stored_object)
-                .map(BasicOutput::from)
-                .map_err(|e| {
-                    error!("failed to convert stored object to basic output: {e}");
-                    ApiError::InternalServerError
-                })
+            let ownership = Ownership::from(&stored_object);
+            stored_object_to_basic_output(stored_object, encoding)
+                .map(|output| BasicOutputWithOwnership { ownership, output })
         })
         .collect()
 }
 
+fn stored_object_to_basic_output(
+    stored_object: StoredObject,
+    encoding: &EncodingParams,
+) -> Result<BasicOutput, ApiError> {
+    iota_types::stardust::output::basic::BasicOutput::try_from(stored_object)
+        .map(|output| BasicOutput::encoded(output, encoding))
+        .map_err(|e| {
+            error!("failed to convert stored object to basic output: {e}");
+            ApiError::InternalServerError
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
 
-    use iota_types::base_types::ObjectID;
+    use diesel::{RunQueryDsl, insert_into};
+    use iota_types::{base_types::ObjectID, object::Owner};
     use tokio_util::sync::CancellationToken;
     use tracing::Level;
     use tracing_subscriber::FmtSubscriber;
 
     use crate::{
         db::{ConnectionPool, Name},
+        models::StoredObject,
         rest::{
             routes::{
-                test_utils::{create_and_insert_basic_output, get_free_port_for_testing_only},
-                v1::{basic::BasicOutput, ensure_checkpoint_is_set},
+                test_utils::{
+                    create_and_insert_basic_output, create_basic_output,
+                    get_free_port_for_testing_only,
+                },
+                v1::{
+                    basic::{BasicOutput, BasicOutputWithOwnership, Ownership},
+                    ensure_checkpoint_is_set,
+                },
             },
             spawn_rest_server,
         },
+        schema::{basic_output_features::dsl::basic_output_features, objects::dsl::objects},
     };
 
     #[tokio::test]
@@ -186,6 +398,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{}", bind_port).parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
@@ -228,6 +442,345 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn get_basic_object_by_id() -> Result<(), anyhow::Error> {
+        let test_db = "stored_basic_object_by_id_test.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut connection = pool.get_connection().unwrap();
+
+        let owner_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let with_expiration =
+            create_and_insert_basic_output(&mut connection, owner_address, 100, 100)?;
+        let without_expiration = iota_types::stardust::output::basic::BasicOutput {
+            expiration: None,
+            ..create_basic_output(owner_address, 200, 0)
+        };
+        insert_into(objects)
+            .values(&StoredObject::new_basic_for_testing(
+                without_expiration.clone(),
+            )?)
+            .execute(&mut connection)?;
+        drop(connection);
+
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        for output in [with_expiration, without_expiration.clone()] {
+            let resp = reqwest::get(format!(
+                "http://127.0.0.1:{}/v1/basic/by-id/{}",
+                bind_port,
+                output.id.object_id()
+            ))
+            .await?
+            .error_for_status()?;
+            assert_eq!(resp.json::<BasicOutput>().await?, BasicOutput::from(output));
+        }
+
+        // Only the output with an expiration unlock condition refers to the address
+        let resp = reqwest::get(format!(
+            "http://127.0.0.1:{}/v1/basic/{}",
+            bind_port, owner_address
+        ))
+        .await?;
+        assert_eq!(resp.json::<Vec<BasicOutput>>().await?.len(), 1);
+
+        let resp = reqwest::get(format!(
+            "http://127.0.0.1:{}/v1/basic/by-id/{}",
+            bind_port,
+            ObjectID::random()
+        ))
+        .await?;
+        assert_eq!(resp.status(), 404);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_basic_object_with_encoded_bytes() -> Result<(), anyhow::Error> {
+        let test_db = "stored_basic_object_encoded_bytes_test.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut connection = pool.get_connection().unwrap();
+
+        let owner_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let basic_output = iota_types::stardust::output::basic::BasicOutput {
+            metadata: Some(br#"{"memo":"deposit"}"#.to_vec()),
+            tag: Some(b"tag\xff".to_vec()),
+            ..create_basic_output(owner_address, 100, 100)
+        };
+        insert_into(objects)
+            .values(&StoredObject::new_basic_for_testing(basic_output.clone())?)
+            .execute(&mut connection)?;
+        drop(connection);
+
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let get = |query: &'static str| {
+            let object_id = *basic_output.id.object_id();
+            async move {
+                reqwest::get(format!(
+                    "http://127.0.0.1:{bind_port}/v1/basic/by-id/{object_id}{query}"
+                ))
+                .await?
+                .error_for_status()?
+                .json::<serde_json::Value>()
+                .await
+            }
+        };
+
+        // Hex by default
+        let output = get("").await?;
+        assert_eq!(output["tag"], "0x746167ff");
+        assert_eq!(
+            output["metadata"],
+            format!("0x{}", hex::encode(br#"{"memo":"deposit"}"#))
+        );
+        assert!(output.get("tag_valid_utf8").is_none());
+        assert!(output.get("metadata_json").is_none());
+
+        let output = get("?encoding=base64").await?;
+        assert_eq!(output["tag"], "dGFn/w==");
+
+        let output = get("?encoding=utf8&decode_metadata=true").await?;
+        assert_eq!(output["tag"], "tag\u{fffd}");
+        assert_eq!(output["tag_valid_utf8"], false);
+        assert_eq!(output["metadata"], r#"{"memo":"deposit"}"#);
+        assert_eq!(output["metadata_valid_utf8"], true);
+        assert_eq!(output["metadata_json"]["memo"], "deposit");
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_all_basic_objects_of_address() -> Result<(), anyhow::Error> {
+        let test_db = "stored_basic_object_all_test.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut connection = pool.get_connection().unwrap();
+
+        let owner_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let other_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let shared = create_and_insert_basic_output(&mut connection, owner_address, 100, 100)?;
+        let mut owned = vec![];
+        for address in [owner_address, other_address] {
+            let basic_output = iota_types::stardust::output::basic::BasicOutput {
+                expiration: None,
+                ..create_basic_output(address, 200, 0)
+            };
+            insert_into(objects)
+                .values(&StoredObject::new_basic_with_owner_for_testing(
+                    basic_output.clone(),
+                    Owner::AddressOwner(address),
+                )?)
+                .execute(&mut connection)?;
+            owned.push(basic_output);
+        }
+        drop(connection);
+
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        // The owned outputs come first
+        let resp = reqwest::get(format!(
+            "http://127.0.0.1:{}/v1/basic/all/{}",
+            bind_port, owner_address
+        ))
+        .await?;
+        let outputs: Vec<BasicOutputWithOwnership> = resp.json().await?;
+        assert_eq!(
+            outputs,
+            vec![
+                BasicOutputWithOwnership {
+                    ownership: Ownership::AddressOwned,
+                    output: owned[0].clone().into(),
+                },
+                BasicOutputWithOwnership {
+                    ownership: Ownership::Shared,
+                    output: shared.into(),
+                },
+            ]
+        );
+
+        // Pages span both kinds of outputs
+        let resp = reqwest::get(format!(
+            "http://127.0.0.1:{}/v1/basic/all/{}?page=2&page_size=1",
+            bind_port, owner_address
+        ))
+        .await?;
+        let outputs: Vec<BasicOutputWithOwnership> = resp.json().await?;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].ownership, Ownership::Shared);
+
+        // The owned outputs are only listed along with the shared ones
+        let resp = reqwest::get(format!(
+            "http://127.0.0.1:{}/v1/basic/{}",
+            bind_port, other_address
+        ))
+        .await?;
+        assert!(resp.json::<Vec<BasicOutput>>().await?.is_empty());
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_basic_objects_by_tag_and_sender() -> Result<(), anyhow::Error> {
+        let test_db = "stored_basic_object_by_tag_and_sender_test.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut connection = pool.get_connection().unwrap();
+
+        let owner_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let sender_address: iota_types::base_types::IotaAddress = ObjectID::random().into();
+        let mut tagged = vec![];
+        for (i, tag) in [&b"EXCHANGE-1"[..], b"EXCHANGE-2", b"OTHER", &[0xff, 0xff]]
+            .into_iter()
+            .enumerate()
+        {
+            let basic_output = iota_types::stardust::output::basic::BasicOutput {
+                tag: Some(tag.to_vec()),
+                sender: (i == 0).then_some(sender_address),
+                ..create_basic_output(owner_address, 100 + i as u64, 0)
+            };
+            let stored_object = StoredObject::new_basic_with_owner_for_testing(
+                basic_output.clone(),
+                Owner::AddressOwner(owner_address),
+            )?;
+            insert_into(objects)
+                .values(&stored_object)
+                .execute(&mut connection)?;
+            insert_into(basic_output_features)
+                .values(&stored_object.basic_output_features()?.unwrap())
+                .execute(&mut connection)?;
+            tagged.push(BasicOutputWithOwnership {
+                ownership: Ownership::AddressOwned,
+                output: basic_output.into(),
+            });
+        }
+        drop(connection);
+
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let get = |path: String| async move {
+            reqwest::get(format!("http://127.0.0.1:{bind_port}/v1/basic/{path}"))
+                .await?
+                .error_for_status()?
+                .json::<Vec<BasicOutputWithOwnership>>()
+                .await
+        };
+
+        assert_eq!(get("by-tag/EXCHANGE-2".to_owned()).await?, tagged[1..2]);
+        assert!(get("by-tag/EXCHANGE".to_owned()).await?.is_empty());
+        assert_eq!(
+            get("by-tag/EXCHANGE?prefix=true".to_owned()).await?,
+            tagged[..2]
+        );
+        assert_eq!(
+            get("by-tag/EXCHANGE?prefix=true&page=2&page_size=1".to_owned()).await?,
+            tagged[1..2]
+        );
+        assert_eq!(
+            get("by-tag/0xff?prefix=true".to_owned()).await?,
+            tagged[3..]
+        );
+        assert_eq!(
+            get(format!("by-tag/0x{}", hex::encode("OTHER"))).await?,
+            tagged[2..3]
+        );
+        assert_eq!(
+            get(format!("by-sender/{sender_address}")).await?,
+            tagged[..1]
+        );
+
+        let resp =
+            reqwest::get(format!("http://127.0.0.1:{bind_port}/v1/basic/by-tag/0xzz")).await?;
+        assert_eq!(resp.status(), 400);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn get_basic_objects_by_address_resolved() -> Result<(), anyhow::Error> {
         ensure_checkpoint_is_set();
@@ -281,6 +834,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{port}").parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
@@ -353,6 +908,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{}", bind_port).parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
@@ -1,54 +1,160 @@
 // Copyright (c) 2024 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use std::sync::atomic::Ordering;
-
-use axum::Router;
-use diesel::{JoinOnDsl, dsl::sql, prelude::*, sql_types::BigInt};
+use axum::{Router, middleware};
+use diesel::{
+    JoinOnDsl,
+    dsl::sql,
+    prelude::*,
+    query_dsl::positional_order_dsl::PositionalOrderDsl,
+    sql_types::{BigInt, Integer},
+};
+use iota_types::base_types::ObjectID;
 use serde::Deserialize;
 use tracing::error;
+use utoipa::IntoParams;
 
 use crate::{
-    models::{ObjectType, StoredObject},
-    rest::{State, error::ApiError},
+    metrics::start_db_query_timer,
+    models::{IotaAddress, ObjectType, StoredObject},
+    rest::{RESPONSE_CACHE, State, cache::CacheKey, error::ApiError, middleware::checkpoint_stamp},
     schema::{expiration_unlock_conditions::dsl::*, objects::dsl::*},
-    sync::LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS,
+    sync::latest_checkpoint_timestamp_ms,
 };
 
 pub(crate) mod basic;
 pub(crate) mod nft;
+pub(crate) mod status;
 
 pub(crate) fn router() -> Router {
-    Router::new().nest("/v1", basic::router().merge(nft::router()))
+    Router::new().nest(
+        "/v1",
+        basic::router()
+            .merge(nft::router())
+            .merge(status::router())
+            .layer(middleware::from_fn(checkpoint_stamp)),
+    )
 }
 
+#[tracing::instrument(skip(pagination, state), err)]
 fn fetch_stored_objects(
     address: iota_types::base_types::IotaAddress,
     pagination: PaginationParams,
     state: State,
     object_type_filter: ObjectType,
     resolve_expiration_uc: bool,
+    include_address_owned: bool,
 ) -> Result<Vec<StoredObject>, ApiError> {
+    let resolved_at_ms = if resolve_expiration_uc {
+        // Latest checkpoint unix timestamp in milliseconds
+        let checkpoint_unix_timestamp_ms = latest_checkpoint_timestamp_ms().ok_or(
+            ApiError::ServiceUnavailable("latest checkpoint not synced yet".to_string()),
+        )? as i64; // Convert to i64 for Diesel
+        Some(checkpoint_unix_timestamp_ms)
+    } else {
+        None
+    };
+
+    let cache_key = CacheKey::new(
+        object_type_filter,
+        address,
+        &pagination,
+        resolved_at_ms,
+        include_address_owned,
+    );
+    let cache_generation = match RESPONSE_CACHE.get().map(|cache| cache.get(&cache_key)) {
+        Some(Ok(stored_objects)) => return Ok(stored_objects),
+        Some(Err(generation)) => Some(generation),
+        None => None,
+    };
+
+    let mut conn = state.connection_pool.get_connection().map_err(|e| {
+        error!("failed to get connection: {e}");
+        ApiError::ServiceUnavailable(format!("failed to get connection: {e}"))
+    })?;
+
+    let timer = start_db_query_timer("fetch_stored_objects");
+    let stored_objects = load_stored_objects(
+        &mut conn,
+        address,
+        &pagination,
+        object_type_filter,
+        resolved_at_ms,
+        include_address_owned,
+    )
+    .map_err(|e| {
+        error!("failed to load stored objects: {e}");
+        ApiError::InternalServerError
+    })?;
+    drop(timer);
+
+    if let (Some(cache), Some(generation)) = (RESPONSE_CACHE.get(), cache_generation) {
+        cache.insert(cache_key, stored_objects.clone(), generation);
+    }
+
+    Ok(stored_objects)
+}
+
+/// Fetch the object of the given type by its ID, whether it has an expiration
+/// unlock condition or not.
+#[tracing::instrument(skip(state), err)]
+fn fetch_stored_object(
+    output_id: ObjectID,
+    state: State,
+    object_type_filter: ObjectType,
+) -> Result<StoredObject, ApiError> {
     let mut conn = state.connection_pool.get_connection().map_err(|e| {
         error!("failed to get connection: {e}");
         ApiError::ServiceUnavailable(format!("failed to get connection: {e}"))
     })?;
 
+    let _timer = start_db_query_timer("fetch_stored_object");
+    objects
+        .find(IotaAddress::from(output_id))
+        .filter(object_type.eq(object_type_filter))
+        .select(StoredObject::as_select())
+        .first::<StoredObject>(&mut conn)
+        .optional()
+        .map_err(|e| {
+            error!("failed to load stored object: {e}");
+            ApiError::InternalServerError
+        })?
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "no {} output with ID {output_id}",
+                object_type_filter.label()
+            ))
+        })
+}
+
+/// The position of `objects.id` in the result columns of
+/// [`load_stored_objects`].
+const OBJECT_ID_COLUMN: u32 = 1;
+/// The position of the ownership rank following the columns of the objects in
+/// [`load_stored_objects`].
+const OWNERSHIP_RANK_COLUMN: u32 = 5;
+
+/// Load the objects of the given type whose expiration unlock condition refers
+/// to the address, preceded by the ones owned by the address if
+/// `include_address_owned` is set, in which case both are ordered by ID.
+///
+/// If `resolved_at_ms` is set, only the objects the address can unlock at that
+/// unix timestamp in milliseconds are returned.
+pub(crate) fn load_stored_objects(
+    conn: &mut SqliteConnection,
+    address: iota_types::base_types::IotaAddress,
+    pagination: &PaginationParams,
+    object_type_filter: ObjectType,
+    resolved_at_ms: Option<i64>,
+    include_address_owned: bool,
+) -> QueryResult<Vec<StoredObject>> {
     let mut base_query = objects
         .inner_join(expiration_unlock_conditions.on(id.eq(object_id)))
         .select(StoredObject::as_select())
         .filter(object_type.eq(object_type_filter))
         .into_boxed();
 
-    if resolve_expiration_uc {
-        // Latest checkpoint unix timestamp in milliseconds
-        let checkpoint_unix_timestamp_ms = LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS
-            .get()
-            .ok_or(ApiError::ServiceUnavailable(
-                "latest checkpoint not synced yet".to_string(),
-            ))?
-            .load(Ordering::SeqCst) as i64; // Convert to i64 for Diesel
-
+    if let Some(checkpoint_unix_timestamp_ms) = resolved_at_ms {
         base_query =
             base_query.filter(
                 owner
@@ -70,44 +176,222 @@ fn fetch_stored_objects(
         );
     }
 
-    // Set default values for pagination if not provided
-    let page = pagination.page.unwrap_or(1);
-    let page_size = pagination.page_size.unwrap_or(10);
+    if include_address_owned {
+        // Ranked by ownership in an extra column, as the order of a compound
+        // select can only refer to its result columns
+        let stored_objects = objects
+            .select((StoredObject::as_select(), sql::<Integer>("0")))
+            .filter(object_type.eq(object_type_filter))
+            .filter(address_owner.eq(address.to_vec()))
+            .into_boxed()
+            .union_all(base_query.select((StoredObject::as_select(), sql::<Integer>("1"))))
+            .positional_order_by((OWNERSHIP_RANK_COLUMN, OBJECT_ID_COLUMN))
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load::<(StoredObject, i32)>(conn)?;
+        return Ok(stored_objects
+            .into_iter()
+            .map(|(stored_object, _)| stored_object)
+            .collect());
+    }
 
-    // Calculate the offset
-    let offset = (page - 1) * page_size;
+    base_query
+        .limit(pagination.limit()) // Limit the number of results
+        .offset(pagination.offset()) // Skip the results for previous pages
+        .load::<StoredObject>(conn)
+}
 
-    let stored_objects = base_query
-        .limit(page_size as i64) // Limit the number of results
-        .offset(offset as i64) // Skip the results for previous pages
-        .load::<StoredObject>(&mut conn)
-        .map_err(|e| {
-            error!("failed to load stored objects: {e}");
-            ApiError::InternalServerError
-        })?;
+/// A search of the basic outputs by one of their features.
+#[derive(Clone, Debug)]
+pub(crate) enum FeatureFilter {
+    Sender(iota_types::base_types::IotaAddress),
+    Tag(Vec<u8>),
+    TagPrefix(Vec<u8>),
+}
 
-    Ok(stored_objects)
+/// Fetch the basic outputs matching the feature filter, whether they are
+/// shared or address-owned.
+#[tracing::instrument(skip(pagination, state), err)]
+fn fetch_basic_objects_by_feature(
+    filter: FeatureFilter,
+    pagination: PaginationParams,
+    state: State,
+) -> Result<Vec<StoredObject>, ApiError> {
+    let mut conn = state.connection_pool.get_connection().map_err(|e| {
+        error!("failed to get connection: {e}");
+        ApiError::ServiceUnavailable(format!("failed to get connection: {e}"))
+    })?;
+
+    let _timer = start_db_query_timer("fetch_basic_objects_by_feature");
+    load_basic_objects_by_feature(&mut conn, &filter, &pagination).map_err(|e| {
+        error!("failed to load stored objects: {e}");
+        ApiError::InternalServerError
+    })
 }
 
-#[derive(Deserialize)]
-struct PaginationParams {
-    page: Option<u32>,
-    page_size: Option<u32>,
+/// Load the basic outputs matching the feature filter, in the order of the
+/// searched feature.
+pub(crate) fn load_basic_objects_by_feature(
+    conn: &mut SqliteConnection,
+    filter: &FeatureFilter,
+    pagination: &PaginationParams,
+) -> QueryResult<Vec<StoredObject>> {
+    use crate::schema::basic_output_features::dsl as features;
+
+    let mut query = objects
+        .inner_join(features::basic_output_features.on(id.eq(features::object_id)))
+        .select(StoredObject::as_select())
+        .into_boxed();
+    query = match filter {
+        FeatureFilter::Sender(sender) => query
+            .filter(features::sender.eq(sender.to_vec()))
+            .order(features::object_id),
+        FeatureFilter::Tag(tag) => query
+            .filter(features::tag.eq(tag))
+            .order(features::object_id),
+        FeatureFilter::TagPrefix(prefix) => {
+            // The tags starting with the prefix sort between it and its successor
+            query = query.filter(features::tag.ge(prefix));
+            if let Some(end) = prefix_successor(prefix) {
+                query = query.filter(features::tag.lt(end));
+            }
+            query.order((features::tag, features::object_id))
+        }
+    };
+
+    query
+        .limit(pagination.limit())
+        .offset(pagination.offset())
+        .load::<StoredObject>(conn)
+}
+
+/// The smallest byte string greater than all the ones starting with the
+/// prefix, if any.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != u8::MAX)?;
+    let mut successor = prefix[..=last].to_vec();
+    successor[last] += 1;
+    Some(successor)
+}
+
+#[derive(Deserialize, Default)]
+pub(crate) struct PaginationParams {
+    pub(crate) page: Option<u32>,
+    pub(crate) page_size: Option<u32>,
+}
+
+/// How the bytes of the basic outputs are returned.
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct EncodingParams {
+    /// Encoding of the `metadata` and `tag` bytes: `hex` (`0x`-prefixed),
+    /// `base64`, or `utf8` (lossy, with the `metadata_valid_utf8` and
+    /// `tag_valid_utf8` validity flags). Defaults to `hex`.
+    pub(crate) encoding: Option<responses::BytesEncoding>,
+    /// Whether to also return the metadata parsed as JSON in `metadata_json`,
+    /// if it is valid JSON. Defaults to false.
+    pub(crate) decode_metadata: Option<bool>,
+}
+
+impl PaginationParams {
+    /// The number of results per page, 10 by default.
+    fn limit(&self) -> i64 {
+        self.page_size.unwrap_or(10) as i64
+    }
+
+    /// The number of results of the previous pages, the first page being
+    /// the default.
+    fn offset(&self) -> i64 {
+        self.page.unwrap_or(1).saturating_sub(1) as i64 * self.limit()
+    }
 }
 
 pub(crate) mod responses {
+    use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
     use serde::{Deserialize, Serialize};
     use utoipa::ToSchema;
 
+    use super::EncodingParams;
     use crate::impl_into_response;
 
     #[derive(Clone, Debug, Serialize, ToSchema)]
     pub(crate) struct BasicOutputVec(pub(crate) Vec<BasicOutput>);
-    impl_into_response!(BasicOutputVec);
+    impl_into_response!(BasicOutputVec, BasicOutput);
 
     #[derive(Clone, Debug, Serialize, ToSchema)]
     pub(crate) struct NftOutputVec(pub(crate) Vec<NftOutput>);
-    impl_into_response!(NftOutputVec);
+    impl_into_response!(NftOutputVec, NftOutput);
+
+    #[derive(Clone, Debug, Serialize, ToSchema)]
+    pub(crate) struct BasicOutputWithOwnershipVec(pub(crate) Vec<BasicOutputWithOwnership>);
+    impl_into_response!(BasicOutputWithOwnershipVec);
+
+    #[derive(Clone, Debug, Serialize, ToSchema)]
+    pub(crate) struct NftOutputWithOwnershipVec(pub(crate) Vec<NftOutputWithOwnership>);
+    impl_into_response!(NftOutputWithOwnershipVec);
+
+    /// How an output is held.
+    #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum Ownership {
+        /// A shared object, unlocked according to its expiration unlock
+        /// condition.
+        Shared,
+        /// An object owned by an address.
+        AddressOwned,
+    }
+
+    impl From<&crate::models::StoredObject> for Ownership {
+        fn from(stored_object: &crate::models::StoredObject) -> Self {
+            match stored_object.address_owner {
+                Some(_) => Self::AddressOwned,
+                None => Self::Shared,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+    pub(crate) struct BasicOutputWithOwnership {
+        pub(crate) ownership: Ownership,
+        #[serde(flatten)]
+        pub(crate) output: BasicOutput,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+    pub(crate) struct NftOutputWithOwnership {
+        pub(crate) ownership: Ownership,
+        #[serde(flatten)]
+        pub(crate) output: NftOutput,
+    }
+
+    /// The encoding of the `metadata` and `tag` bytes.
+    #[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub(crate) enum BytesEncoding {
+        /// `0x`-prefixed hex.
+        #[default]
+        Hex,
+        /// Standard base64, with padding.
+        Base64,
+        /// UTF-8 text, the invalid sequences being replaced with `U+FFFD`.
+        Utf8,
+    }
+
+    impl BytesEncoding {
+        fn encode(self, bytes: &[u8]) -> String {
+            match self {
+                Self::Hex => format!("0x{}", hex::encode(bytes)),
+                Self::Base64 => BASE64.encode(bytes),
+                Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            }
+        }
+
+        /// Whether the bytes are valid UTF-8, only told for the `utf8`
+        /// encoding.
+        fn valid_utf8(self, bytes: &[u8]) -> Option<bool> {
+            (self == Self::Utf8).then(|| std::str::from_utf8(bytes).is_ok())
+        }
+    }
 
     #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
     pub(crate) struct BasicOutput {
@@ -117,8 +401,20 @@ pub(crate) mod responses {
         pub(crate) storage_deposit_return: Option<StorageDepositReturn>,
         pub(crate) timelock: Option<Timelock>,
         pub(crate) expiration: Option<Expiration>,
-        pub(crate) metadata: Option<Vec<u8>>,
-        pub(crate) tag: Option<Vec<u8>>,
+        /// The metadata feature, in the requested encoding.
+        pub(crate) metadata: Option<String>,
+        /// Whether the metadata is valid UTF-8, only set with the `utf8`
+        /// encoding.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub(crate) metadata_valid_utf8: Option<bool>,
+        /// The metadata parsed as JSON, only set if requested and valid.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub(crate) metadata_json: Option<serde_json::Value>,
+        /// The tag feature, in the requested encoding.
+        pub(crate) tag: Option<String>,
+        /// Whether the tag is valid UTF-8, only set with the `utf8` encoding.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub(crate) tag_valid_utf8: Option<bool>,
         pub(crate) sender: Option<String>,
     }
 
@@ -163,6 +459,22 @@ pub(crate) mod responses {
 
     impl From<iota_types::stardust::output::basic::BasicOutput> for BasicOutput {
         fn from(output: iota_types::stardust::output::basic::BasicOutput) -> Self {
+            Self::encoded(output, &Default::default())
+        }
+    }
+
+    impl BasicOutput {
+        /// Convert the output, encoding its bytes as requested.
+        pub(crate) fn encoded(
+            output: iota_types::stardust::output::basic::BasicOutput,
+            params: &EncodingParams,
+        ) -> Self {
+            let encoding = params.encoding.unwrap_or_default();
+            let metadata_json = output
+                .metadata
+                .as_deref()
+                .filter(|_| params.decode_metadata.unwrap_or_default())
+                .and_then(|metadata| serde_json::from_slice(metadata).ok());
             Self {
                 id: output.id.object_id().to_string(),
                 balance: Balance {
@@ -186,8 +498,14 @@ pub(crate) mod responses {
                     return_address: x.return_address.to_string(),
                     unix_time: x.unix_time as u64,
                 }),
-                metadata: output.metadata,
-                tag: output.tag,
+                metadata: output.metadata.as_deref().map(|x| encoding.encode(x)),
+                metadata_valid_utf8: output
+                    .metadata
+                    .as_deref()
+                    .and_then(|x| encoding.valid_utf8(x)),
+                metadata_json,
+                tag: output.tag.as_deref().map(|x| encoding.encode(x)),
+                tag_valid_utf8: output.tag.as_deref().and_then(|x| encoding.valid_utf8(x)),
                 sender: output.sender.map(|x| x.to_string()),
             }
         }
@@ -230,7 +548,10 @@ pub(crate) fn ensure_checkpoint_is_set() {
         atomic::{AtomicU64, Ordering},
     };
 
+    use crate::sync::{LATEST_CHECKPOINT_SEQUENCE_NUMBER, LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS};
+
     const DEFAULT_CHECKPOINT_UNIX_TIMESTAMP_MS_FOR_TESTING: u64 = 500_000_000;
+    const DEFAULT_CHECKPOINT_SEQUENCE_NUMBER_FOR_TESTING: u64 = 100;
 
     static INIT: Once = Once::new();
     INIT.call_once(|| {
@@ -246,5 +567,11 @@ pub(crate) fn ensure_checkpoint_is_set() {
                 Ordering::SeqCst,
             );
         }
+        LATEST_CHECKPOINT_SEQUENCE_NUMBER
+            .get_or_init(|| AtomicU64::new(0))
+            .store(
+                DEFAULT_CHECKPOINT_SEQUENCE_NUMBER_FOR_TESTING,
+                Ordering::SeqCst,
+            );
     });
 }
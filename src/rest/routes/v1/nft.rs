@@ -11,8 +11,11 @@ use crate::{
         error::ApiError,
         extractors::Path,
         routes::v1::{
-            PaginationParams, fetch_stored_objects,
-            responses::{NftOutput, NftOutputVec},
+            PaginationParams, fetch_stored_object, fetch_stored_objects,
+            responses::{
+                NftOutput, NftOutputVec, NftOutputWithOwnership, NftOutputWithOwnershipVec,
+                Ownership,
+            },
         },
     },
 };
@@ -21,6 +24,8 @@ pub(crate) fn router() -> Router {
     Router::new()
         .route("/nft/:address", get(nft))
         .route("/nft/resolved/:address", get(resolved))
+        .route("/nft/by-id/:object_id", get(by_id))
+        .route("/nft/all/:address", get(all))
 }
 
 /// Get the `NftOutput`s owned by the address
@@ -36,7 +41,9 @@ description =
         (status = 400, description = "Bad request"),
         (status = 500, description = "Internal server error"),
         (status = 503, description = "Service unavailable"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
     ),
     params(
         ("address" = String, Path, description = "The hexadecimal address for which to fetch NFT outputs."),
@@ -49,7 +56,8 @@ async fn nft(
     Query(pagination): Query<PaginationParams>,
     Extension(state): Extension<State>,
 ) -> Result<NftOutputVec, ApiError> {
-    let stored_objects = fetch_stored_objects(address, pagination, state, ObjectType::Nft, false)?;
+    let stored_objects =
+        fetch_stored_objects(address, pagination, state, ObjectType::Nft, false, false)?;
     let nft_outputs = stored_objects_to_nft_outputs(stored_objects)?;
     Ok(NftOutputVec(nft_outputs))
 }
@@ -77,7 +85,9 @@ description =
         (status = 400, description = "Bad request"),
         (status = 500, description = "Internal server error"),
         (status = 503, description = "Service unavailable"),
-        (status = 403, description = "Forbidden")
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
     ),
     params(
         ("address" = String, Path, description = "The hexadecimal address for which to fetch NFT outputs."),
@@ -90,27 +100,102 @@ async fn resolved(
     Query(pagination): Query<PaginationParams>,
     Extension(state): Extension<State>,
 ) -> Result<NftOutputVec, ApiError> {
-    let stored_objects = fetch_stored_objects(address, pagination, state, ObjectType::Nft, true)?;
+    let stored_objects =
+        fetch_stored_objects(address, pagination, state, ObjectType::Nft, true, false)?;
     let nft_outputs = stored_objects_to_nft_outputs(stored_objects)?;
     Ok(NftOutputVec(nft_outputs))
 }
 
+/// Get the `NftOutput`s owned by the address, or whose expiration unlock
+/// condition refers to it.
+#[utoipa::path(
+get,
+path = "/v1/nft/all/{address}",
+description =
+    "Fetches all the NFT outputs of a specified address with optional pagination.
+    It returns the NFT outputs owned by the address, followed by the shared ones with expiration unlock conditions
+    that refer to the address either as the `owner` or as the `return_address`, each ordered by object ID. The `ownership`
+    field tells them apart. Results can be paginated by providing optional `page` and `page_size` query parameters.",
+    responses(
+        (status = 200, description = "Successful request", body = NftOutputWithOwnershipVec),
+        (status = 400, description = "Bad request"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("address" = String, Path, description = "The hexadecimal address for which to fetch NFT outputs."),
+        ("page" = Option<u32>, Query, description = "Page number for pagination. Defaults to 1."),
+        ("page_size" = Option<u32>, Query, description = "Number of items per page for pagination. Defaults to 10.")
+    )
+)]
+async fn all(
+    Path(address): Path<iota_types::base_types::IotaAddress>,
+    Query(pagination): Query<PaginationParams>,
+    Extension(state): Extension<State>,
+) -> Result<NftOutputWithOwnershipVec, ApiError> {
+    let stored_objects =
+        fetch_stored_objects(address, pagination, state, ObjectType::Nft, false, true)?;
+    let nft_outputs = stored_objects
+        .into_iter()
+        .map(|stored_object| {
+            let ownership = Ownership::from(&stored_object);
+            stored_object_to_nft_output(stored_object)
+                .map(|output| NftOutputWithOwnership { ownership, output })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(NftOutputWithOwnershipVec(nft_outputs))
+}
+
+/// Get a `NftOutput` by its object ID
+#[utoipa::path(
+get,
+path = "/v1/nft/by-id/{object_id}",
+description =
+    "Fetches a NFT output by its object ID.
+    Unlike the queries by address, it also returns the outputs without expiration unlock condition.",
+    responses(
+        (status = 200, description = "Successful request", body = NftOutput),
+        (status = 400, description = "Bad request"),
+        (status = 404, description = "Not found"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable"),
+        (status = 403, description = "Forbidden"),
+        (status = 401, description = "Unauthorized"),
+        (status = 429, description = "Too many requests")
+    ),
+    params(
+        ("object_id" = String, Path, description = "The hexadecimal ID of the NFT output.")
+    )
+)]
+async fn by_id(
+    Path(object_id): Path<iota_types::base_types::ObjectID>,
+    Extension(state): Extension<State>,
+) -> Result<NftOutput, ApiError> {
+    let stored_object = fetch_stored_object(object_id, state, ObjectType::Nft)?;
+    stored_object_to_nft_output(stored_object)
+}
+
 fn stored_objects_to_nft_outputs(
     stored_objects: Vec<StoredObject>,
 ) -> Result<Vec<NftOutput>, ApiError> {
     stored_objects
         .into_iter()
-        .map(|stored_object| {
-            iota_types::stardust::output::nft::NftOutput::try_from(stored_object)
-                .map(NftOutput::from)
-                .map_err(|e| {
-                    error!("failed to convert stored object to NFT output: {e}");
-                    ApiError::InternalServerError
-                })
-        })
+        .map(stored_object_to_nft_output)
         .collect()
 }
 
+fn stored_object_to_nft_output(stored_object: StoredObject) -> Result<NftOutput, ApiError> {
+    iota_types::stardust::output::nft::NftOutput::try_from(stored_object)
+        .map(NftOutput::from)
+        .map_err(|e| {
+            error!("failed to convert stored object to NFT output: {e}");
+            ApiError::InternalServerError
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -184,6 +269,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{}", bind_port).parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
@@ -279,6 +366,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{port}").parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
@@ -352,6 +441,8 @@ mod tests {
         let handle = spawn_rest_server(
             format!("127.0.0.1:{}", bind_port).parse().unwrap(),
             pool,
+            Default::default(),
+            None,
             cancel_token.clone(),
         );
 
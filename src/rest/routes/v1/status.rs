@@ -0,0 +1,136 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::Ordering;
+
+use axum::{Extension, Router, routing::get};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+    impl_into_response,
+    metrics::METRICS,
+    rest::{State, error::ApiError},
+    sync::{INDEXED_PACKAGE_IDS, LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS},
+};
+
+pub(crate) fn router() -> Router {
+    Router::new().route("/status", get(status))
+}
+
+/// Get the synchronization status of the Indexer
+#[utoipa::path(
+    get,
+    path = "/v1/status",
+    description = "Fetches the synchronization status of the Indexer, to tell how fresh the served data is.
+    It returns the last received and last indexed checkpoint sequence numbers, the latest checkpoint
+    timestamp used to resolve the expiration unlock conditions, the Indexer version, the indexed
+    package IDs and the database migration version.",
+    responses(
+        (status = 200, description = "Successful request", body = StatusResponse),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Service unavailable")
+    ),
+)]
+async fn status(Extension(state): Extension<State>) -> Result<StatusResponse, ApiError> {
+    let migration_version = state
+        .connection_pool
+        .latest_migration_version()
+        .map_err(|e| {
+            error!("failed to get the migration version: {e}");
+            ApiError::ServiceUnavailable(format!("failed to get the migration version: {e}"))
+        })?;
+
+    let metrics = METRICS.get();
+
+    Ok(StatusResponse {
+        last_received_checkpoint: metrics
+            .map(|metrics| metrics.last_checkpoint_received.get() as u64),
+        last_indexed_checkpoint: metrics
+            .map(|metrics| metrics.last_checkpoint_indexed.get() as u64),
+        latest_checkpoint_timestamp_ms: LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS
+            .get()
+            .map(|timestamp| timestamp.load(Ordering::SeqCst)),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        package_ids: INDEXED_PACKAGE_IDS
+            .get()
+            .map(|package_ids| package_ids.iter().map(ToString::to_string).collect())
+            .unwrap_or_default(),
+        migration_version,
+    })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct StatusResponse {
+    /// The last checkpoint received from the fullnode, if syncing started.
+    pub(crate) last_received_checkpoint: Option<u64>,
+    /// The last indexed checkpoint, if syncing started.
+    pub(crate) last_indexed_checkpoint: Option<u64>,
+    /// The unix timestamp in milliseconds of the latest indexed checkpoint,
+    /// used to resolve the expiration unlock conditions.
+    pub(crate) latest_checkpoint_timestamp_ms: Option<u64>,
+    /// The version of the Indexer.
+    pub(crate) version: String,
+    /// The IDs of the packages whose objects are indexed.
+    pub(crate) package_ids: Vec<String>,
+    /// The version of the latest migration applied to the objects database.
+    pub(crate) migration_version: Option<String>,
+}
+impl_into_response!(StatusResponse);
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::{
+        db::{ConnectionPool, Name},
+        rest::{routes::test_utils::get_free_port_for_testing_only, spawn_rest_server},
+    };
+
+    #[tokio::test]
+    async fn get_status() -> Result<(), anyhow::Error> {
+        let test_db = "get_status.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let latest_migration_version = pool.latest_known_migration_version()?;
+
+        // Spawn the REST server
+        let cancel_token = CancellationToken::new();
+        let bind_port = get_free_port_for_testing_only().unwrap();
+        let handle = spawn_rest_server(
+            format!("127.0.0.1:{}", bind_port).parse().unwrap(),
+            pool,
+            Default::default(),
+            None,
+            cancel_token.clone(),
+        );
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let resp = reqwest::get(format!("http://127.0.0.1:{}/v1/status", bind_port)).await?;
+        assert_eq!(resp.status(), 200);
+
+        let status: StatusResponse = resp.json().await?;
+        assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+        assert!(latest_migration_version.is_some());
+        assert_eq!(status.migration_version, latest_migration_version);
+
+        cancel_token.cancel();
+        handle.await.unwrap();
+
+        // Clean up the test database
+        std::fs::remove_file(test_db).unwrap();
+
+        Ok(())
+    }
+}
@@ -2,6 +2,26 @@
 // SPDX-License-Identifier: Apache-2.0
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    basic_output_features (object_id) {
+        object_id -> Binary,
+        sender -> Nullable<Binary>,
+        tag -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    checkpoint_failures (id) {
+        id -> Integer,
+        checkpoint_sequence_number -> BigInt,
+        transaction_digest -> Nullable<Text>,
+        object_id -> Nullable<Text>,
+        error -> Text,
+        action -> Text,
+        recorded_at_ms -> BigInt,
+    }
+}
+
 diesel::table! {
     expiration_unlock_conditions (object_id) {
         owner -> Binary,
@@ -23,12 +43,16 @@ diesel::table! {
         id -> Binary,
         object_type -> Integer,
         contents -> Binary,
+        address_owner -> Nullable<Binary>,
     }
 }
 
+diesel::joinable!(basic_output_features -> objects (object_id));
 diesel::joinable!(expiration_unlock_conditions -> objects (object_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    basic_output_features,
+    checkpoint_failures,
     expiration_unlock_conditions,
     last_checkpoint_sync,
     objects,
@@ -0,0 +1,409 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Online snapshots of the Indexer databases.
+//!
+//! A snapshot is a directory named after the checkpoint watermark it was taken
+//! at, containing a copy of both the objects database and the progress store,
+//! plus a `metadata.json` file describing it.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use diesel::prelude::*;
+use iota_data_ingestion_core::ProgressStore;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    db::{ConnectionPool, latest_migration_version},
+    models::LastCheckpointSync,
+    schema::last_checkpoint_sync::dsl::*,
+    sync::{SqliteProgressStore, WORKER_TASK_NAME},
+};
+
+/// Version of the snapshot layout, bumped on incompatible changes.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+pub const OBJECTS_DB_FILE: &str = "objects.db";
+pub const PROGRESS_STORE_DB_FILE: &str = "progress_store.db";
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Describes the content of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    pub format_version: u32,
+    /// The checkpoint watermark recorded in the progress store copy.
+    pub checkpoint_sequence_number: u64,
+    pub objects_migration_version: Option<String>,
+    pub progress_store_migration_version: Option<String>,
+    pub created_at_unix_ms: u64,
+}
+
+/// Take a consistent snapshot of both databases into a new directory under
+/// `target_dir`, while the Indexer keeps running.
+///
+/// The progress store is copied before the objects database: since the
+/// watermark is only advanced after a checkpoint has been written, the objects
+/// copy is never behind the recorded watermark, and re-processing the few
+/// checkpoints in between on restore is idempotent.
+pub fn create_snapshot(
+    objects_pool: &ConnectionPool,
+    progress_store_pool: &ConnectionPool,
+    target_dir: &Path,
+) -> Result<(PathBuf, SnapshotMetadata)> {
+    fs::create_dir_all(target_dir)
+        .with_context(|| format!("failed to create directory {}", target_dir.display()))?;
+
+    let staging_dir = target_dir.join(format!(".snapshot-in-progress-{}", std::process::id()));
+    fs::create_dir(&staging_dir)
+        .with_context(|| format!("failed to create directory {}", staging_dir.display()))?;
+
+    let result =
+        write_snapshot(objects_pool, progress_store_pool, &staging_dir).and_then(|metadata| {
+            let snapshot_dir = target_dir.join(format!(
+                "checkpoint-{}",
+                metadata.checkpoint_sequence_number
+            ));
+            if snapshot_dir.exists() {
+                anyhow::bail!("snapshot {} already exists", snapshot_dir.display());
+            }
+            fs::rename(&staging_dir, &snapshot_dir)?;
+            Ok((snapshot_dir, metadata))
+        });
+
+    if result.is_err() {
+        _ = fs::remove_dir_all(&staging_dir);
+    }
+
+    let (snapshot_dir, metadata) = result?;
+    info!(
+        "Snapshot at checkpoint {} written to {}",
+        metadata.checkpoint_sequence_number,
+        snapshot_dir.display()
+    );
+
+    Ok((snapshot_dir, metadata))
+}
+
+fn write_snapshot(
+    objects_pool: &ConnectionPool,
+    progress_store_pool: &ConnectionPool,
+    dir: &Path,
+) -> Result<SnapshotMetadata> {
+    let progress_store_copy = dir.join(PROGRESS_STORE_DB_FILE);
+    progress_store_pool.backup_to(&progress_store_copy)?;
+    let objects_copy = dir.join(OBJECTS_DB_FILE);
+    objects_pool.backup_to(&objects_copy)?;
+
+    // Described from the copies, as the live databases may have been migrated
+    // or synced further in the meantime
+    let mut progress_store_conn = establish(&progress_store_copy)?;
+    let mut objects_conn = establish(&objects_copy)?;
+    let metadata = SnapshotMetadata {
+        format_version: SNAPSHOT_FORMAT_VERSION,
+        checkpoint_sequence_number: read_watermark(&mut progress_store_conn)?,
+        objects_migration_version: latest_migration_version(&mut objects_conn)?,
+        progress_store_migration_version: latest_migration_version(&mut progress_store_conn)?,
+        created_at_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+    };
+
+    fs::write(
+        dir.join(METADATA_FILE),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    Ok(metadata)
+}
+
+/// Load a snapshot into the given databases, replacing their content.
+///
+/// The snapshot format version and schema migration level are checked first,
+/// then the objects database is restored, migrated to the latest schema, and
+/// the snapshot watermark is recorded in the progress store so that syncing
+/// resumes from the snapshot checkpoint.
+pub async fn restore_snapshot(
+    snapshot_dir: &Path,
+    objects_pool: &ConnectionPool,
+    progress_store_pool: &ConnectionPool,
+) -> Result<SnapshotMetadata> {
+    let metadata = read_metadata(snapshot_dir)?;
+    if metadata.format_version != SNAPSHOT_FORMAT_VERSION {
+        anyhow::bail!(
+            "unsupported snapshot format version {}, expected {SNAPSHOT_FORMAT_VERSION}",
+            metadata.format_version
+        );
+    }
+
+    let known_migration_version = objects_pool.latest_known_migration_version()?;
+    if metadata.objects_migration_version > known_migration_version {
+        anyhow::bail!(
+            "snapshot schema version {:?} is newer than the latest supported {:?}",
+            metadata.objects_migration_version,
+            known_migration_version
+        );
+    }
+
+    let objects_file = snapshot_dir.join(OBJECTS_DB_FILE);
+    if !objects_file.is_file() {
+        anyhow::bail!("snapshot is missing {}", objects_file.display());
+    }
+
+    objects_pool.restore_from(&objects_file)?;
+    let restored_migration_version = objects_pool.latest_migration_version()?;
+    if restored_migration_version != metadata.objects_migration_version {
+        anyhow::bail!(
+            "restored schema version {restored_migration_version:?} does not match the snapshot metadata {:?}",
+            metadata.objects_migration_version
+        );
+    }
+    objects_pool.run_migrations()?;
+
+    progress_store_pool.run_migrations()?;
+    SqliteProgressStore::new(progress_store_pool.clone())
+        .save(
+            WORKER_TASK_NAME.to_owned(),
+            metadata.checkpoint_sequence_number,
+        )
+        .await?;
+
+    info!(
+        "Restored snapshot at checkpoint {} from {}",
+        metadata.checkpoint_sequence_number,
+        snapshot_dir.display()
+    );
+
+    Ok(metadata)
+}
+
+/// Restore a snapshot only if the Indexer has not synced any checkpoint yet.
+///
+/// Returns `None` if the snapshot was skipped.
+pub async fn bootstrap_from_snapshot(
+    snapshot_dir: &Path,
+    objects_pool: &ConnectionPool,
+    progress_store_pool: &ConnectionPool,
+) -> Result<Option<SnapshotMetadata>> {
+    if has_synced(progress_store_pool).await? {
+        warn!(
+            "Indexer already synced, ignoring snapshot {}",
+            snapshot_dir.display()
+        );
+        return Ok(None);
+    }
+
+    restore_snapshot(snapshot_dir, objects_pool, progress_store_pool)
+        .await
+        .map(Some)
+}
+
+/// Check whether the progress store records any synced checkpoint.
+pub async fn has_synced(progress_store_pool: &ConnectionPool) -> Result<bool> {
+    progress_store_pool.run_migrations()?;
+    let watermark = SqliteProgressStore::new(progress_store_pool.clone())
+        .load(WORKER_TASK_NAME.to_owned())
+        .await?;
+
+    Ok(watermark > 0)
+}
+
+/// Read and parse the metadata file of a snapshot.
+pub fn read_metadata(snapshot_dir: &Path) -> Result<SnapshotMetadata> {
+    let metadata_file = snapshot_dir.join(METADATA_FILE);
+    let content = fs::read(&metadata_file)
+        .with_context(|| format!("failed to read {}", metadata_file.display()))?;
+
+    serde_json::from_slice(&content)
+        .with_context(|| format!("invalid snapshot metadata {}", metadata_file.display()))
+}
+
+/// Open a database file of a snapshot.
+fn establish(file: &Path) -> Result<SqliteConnection> {
+    let path = file
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid path {}", file.display()))?;
+    Ok(SqliteConnection::establish(path)?)
+}
+
+/// Read the checkpoint watermark of the sync worker from a progress store copy.
+fn read_watermark(conn: &mut SqliteConnection) -> Result<u64> {
+    let last_checkpoint = last_checkpoint_sync
+        .select(LastCheckpointSync::as_select())
+        .find(WORKER_TASK_NAME)
+        .first::<LastCheckpointSync>(conn)
+        .optional()?;
+
+    Ok(last_checkpoint
+        .map(|ch| ch.sequence_number as u64)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::insert_into;
+
+    use super::*;
+    use crate::{
+        db::Name, rest::routes::test_utils::create_and_insert_basic_output,
+        schema::objects::dsl::objects,
+    };
+
+    fn remove_test_files(files: &[&str], dir: &Path) {
+        for file in files {
+            if Path::new(file).exists() {
+                fs::remove_file(file).unwrap();
+            }
+        }
+        if dir.exists() {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    /// Create both databases with one basic output and a watermark at
+    /// checkpoint 42.
+    fn populated_pools(
+        objects_db: &str,
+        progress_store_db: &str,
+    ) -> (ConnectionPool, ConnectionPool) {
+        let objects_pool =
+            ConnectionPool::new_with_url(objects_db, Default::default(), Name::Objects).unwrap();
+        objects_pool.run_migrations().unwrap();
+        let progress_store_pool = ConnectionPool::new_with_url(
+            progress_store_db,
+            Default::default(),
+            Name::ProgressStore,
+        )
+        .unwrap();
+        progress_store_pool.run_migrations().unwrap();
+
+        let mut conn = objects_pool.get_connection().unwrap();
+        let owner_address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        create_and_insert_basic_output(&mut conn, owner_address, 100, 100).unwrap();
+        drop(conn);
+
+        insert_into(last_checkpoint_sync)
+            .values(&LastCheckpointSync {
+                sequence_number: 42,
+                task_id: WORKER_TASK_NAME.to_owned(),
+            })
+            .execute(&mut progress_store_pool.get_connection().unwrap())
+            .unwrap();
+
+        (objects_pool, progress_store_pool)
+    }
+
+    #[test]
+    fn create_snapshot_while_pools_are_open() {
+        let objects_db = "create_snapshot_objects.db";
+        let progress_store_db = "create_snapshot_progress_store.db";
+        let target_dir = Path::new("create_snapshot_target");
+        remove_test_files(&[objects_db, progress_store_db], target_dir);
+
+        let (objects_pool, progress_store_pool) = populated_pools(objects_db, progress_store_db);
+
+        let (snapshot_dir, metadata) =
+            create_snapshot(&objects_pool, &progress_store_pool, target_dir).unwrap();
+
+        assert_eq!(snapshot_dir, target_dir.join("checkpoint-42"));
+        assert_eq!(metadata.format_version, SNAPSHOT_FORMAT_VERSION);
+        assert_eq!(metadata.checkpoint_sequence_number, 42);
+        assert_eq!(
+            metadata.objects_migration_version,
+            objects_pool.latest_migration_version().unwrap()
+        );
+        assert_eq!(
+            metadata.progress_store_migration_version,
+            progress_store_pool.latest_migration_version().unwrap()
+        );
+        assert!(metadata.objects_migration_version.is_some());
+        assert_eq!(read_metadata(&snapshot_dir).unwrap(), metadata);
+
+        let mut snapshot_conn =
+            SqliteConnection::establish(snapshot_dir.join(OBJECTS_DB_FILE).to_str().unwrap())
+                .unwrap();
+        let objects_count: i64 = objects.count().get_result(&mut snapshot_conn).unwrap();
+        assert_eq!(objects_count, 1);
+
+        // A second snapshot at the same watermark must not overwrite the first
+        assert!(create_snapshot(&objects_pool, &progress_store_pool, target_dir).is_err());
+
+        // clean-up test dbs
+        remove_test_files(&[objects_db, progress_store_db], target_dir);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_fresh_databases_from_snapshot() {
+        let objects_db = "bootstrap_snapshot_source_objects.db";
+        let progress_store_db = "bootstrap_snapshot_source_progress_store.db";
+        let new_objects_db = "bootstrap_snapshot_objects.db";
+        let new_progress_store_db = "bootstrap_snapshot_progress_store.db";
+        let files = [
+            objects_db,
+            progress_store_db,
+            new_objects_db,
+            new_progress_store_db,
+        ];
+        let target_dir = Path::new("bootstrap_snapshot_target");
+        remove_test_files(&files, target_dir);
+
+        let (objects_pool, progress_store_pool) = populated_pools(objects_db, progress_store_db);
+        let (snapshot_dir, metadata) =
+            create_snapshot(&objects_pool, &progress_store_pool, target_dir).unwrap();
+
+        let new_objects_pool =
+            ConnectionPool::new_with_url(new_objects_db, Default::default(), Name::Objects)
+                .unwrap();
+        let new_progress_store_pool = ConnectionPool::new_with_url(
+            new_progress_store_db,
+            Default::default(),
+            Name::ProgressStore,
+        )
+        .unwrap();
+
+        let restored =
+            bootstrap_from_snapshot(&snapshot_dir, &new_objects_pool, &new_progress_store_pool)
+                .await
+                .unwrap();
+        assert_eq!(restored, Some(metadata));
+
+        let objects_count: i64 = objects
+            .count()
+            .get_result(&mut new_objects_pool.get_connection().unwrap())
+            .unwrap();
+        assert_eq!(objects_count, 1);
+        let watermark = SqliteProgressStore::new(new_progress_store_pool.clone())
+            .load(WORKER_TASK_NAME.to_owned())
+            .await
+            .unwrap();
+        assert_eq!(watermark, 42);
+
+        // An already synced Indexer ignores the snapshot
+        let restored =
+            bootstrap_from_snapshot(&snapshot_dir, &new_objects_pool, &new_progress_store_pool)
+                .await
+                .unwrap();
+        assert_eq!(restored, None);
+
+        // Snapshots with an unknown format are rejected
+        let mut metadata = read_metadata(&snapshot_dir).unwrap();
+        metadata.format_version = SNAPSHOT_FORMAT_VERSION + 1;
+        fs::write(
+            snapshot_dir.join(METADATA_FILE),
+            serde_json::to_string(&metadata).unwrap(),
+        )
+        .unwrap();
+        assert!(
+            restore_snapshot(&snapshot_dir, &new_objects_pool, &new_progress_store_pool)
+                .await
+                .is_err()
+        );
+
+        // clean-up test dbs
+        remove_test_files(&files, target_dir);
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Batching of the database writes of concurrently processed checkpoints, so
+//! that they are committed in a single transaction.
+//!
+//! A checkpoint is only reported as processed once its batch is committed,
+//! so that the progress store never moves past unwritten changes.
+
+use std::{num::NonZeroUsize, time::Duration};
+
+use anyhow::Context;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use crate::{
+    db::ConnectionPool,
+    sync::worker::{CheckpointChanges, write_checkpoints},
+};
+
+type PendingWrite = (CheckpointChanges, oneshot::Sender<Result<(), String>>);
+
+/// Collects the changes of the checkpoints and writes them in batches.
+#[derive(Clone, Debug)]
+pub(crate) struct WriteBatcher {
+    sender: mpsc::Sender<PendingWrite>,
+}
+
+impl WriteBatcher {
+    /// Spawn the task writing batches of up to `size` checkpoints, waiting at
+    /// most `timeout` to fill a batch.
+    ///
+    /// The task stops once all the handles are dropped.
+    pub(crate) fn spawn(pool: ConnectionPool, size: NonZeroUsize, timeout: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel(size.get());
+        tokio::spawn(run(pool, receiver, size.get(), timeout));
+        Self { sender }
+    }
+
+    /// Write the changes of a checkpoint, waiting for their batch to be
+    /// committed.
+    pub(crate) async fn write(&self, changes: CheckpointChanges) -> anyhow::Result<()> {
+        let (done, result) = oneshot::channel();
+        self.sender
+            .send((changes, done))
+            .await
+            .map_err(|_| anyhow::anyhow!("the write batcher stopped"))?;
+        result
+            .await
+            .context("the write batcher stopped")?
+            .map_err(anyhow::Error::msg)
+    }
+}
+
+async fn run(
+    pool: ConnectionPool,
+    mut receiver: mpsc::Receiver<PendingWrite>,
+    size: usize,
+    timeout: Duration,
+) {
+    while let Some(first) = receiver.recv().await {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + timeout;
+        while batch.len() < size {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(pending)) => batch.push(pending),
+                _ => break,
+            }
+        }
+
+        let (changes, done): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+        let pool = pool.clone();
+        let result = tokio::task::spawn_blocking(move || write_checkpoints(&pool, changes))
+            .await
+            .map_err(|e| format!("write task failed: {e}"))
+            .and_then(|result| result.map_err(|e| format!("{e:#}")));
+        for done in done {
+            // The checkpoint processing may have been cancelled meanwhile
+            _ = done.send(result.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use diesel::{QueryDsl, RunQueryDsl};
+
+    use super::*;
+    use crate::{
+        db::Name,
+        metrics::{METRICS, Metrics},
+        models::{IotaAddress, StoredObject},
+        rest::routes::test_utils::create_basic_output,
+        schema::objects::dsl::objects,
+    };
+
+    #[tokio::test]
+    async fn write_concurrent_checkpoints_in_batches() {
+        let test_db = "write_concurrent_checkpoints_in_batches.db";
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+        METRICS.get_or_init(|| Arc::new(Metrics::new(&prometheus::Registry::new())));
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+
+        let stored_objects = (0..4)
+            .map(|_| {
+                let owner = iota_types::base_types::IotaAddress::random_for_testing_only();
+                StoredObject::new_basic_for_testing(create_basic_output(owner, 1, 0)).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let mut conn = pool.get_connection().unwrap();
+
+        let batcher = WriteBatcher::spawn(
+            pool.clone(),
+            NonZeroUsize::new(4).unwrap(),
+            Duration::from_secs(1),
+        );
+        let mut writes = tokio::task::JoinSet::new();
+        for (sequence_number, stored_object) in stored_objects.iter().enumerate() {
+            let batcher = batcher.clone();
+            let changes = CheckpointChanges {
+                sequence_number: sequence_number as u64,
+                created_objects: vec![stored_object.clone()],
+                deleted_addresses: vec![],
+            };
+            writes.spawn(async move { batcher.write(changes).await });
+        }
+        while let Some(result) = writes.join_next().await {
+            result.unwrap().unwrap();
+        }
+        let count: i64 = objects.count().get_result(&mut conn).unwrap();
+        assert_eq!(count, 4);
+
+        // Deleting in a later checkpoint of the same batch wins
+        let created = stored_objects[0].clone();
+        let deleted_address: IotaAddress = created.id;
+        let (delete, create) = tokio::join!(
+            batcher.write(CheckpointChanges {
+                sequence_number: 11,
+                created_objects: vec![],
+                deleted_addresses: vec![deleted_address],
+            }),
+            batcher.write(CheckpointChanges {
+                sequence_number: 10,
+                created_objects: vec![created],
+                deleted_addresses: vec![],
+            }),
+        );
+        delete.unwrap();
+        create.unwrap();
+        let count: i64 = objects.count().get_result(&mut conn).unwrap();
+        assert_eq!(count, 3);
+
+        drop(conn);
+        std::fs::remove_file(test_db).unwrap();
+    }
+}
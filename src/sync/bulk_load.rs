@@ -0,0 +1,321 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bulk load of the genesis objects into an empty database.
+//!
+//! The genesis checkpoint holds every migrated object, so inserting them one
+//! by one with upserts dominates the initial sync. On an empty database they
+//! are instead inserted with multi-row statements in a single transaction,
+//! the secondary indexes being built once all the rows are in.
+
+use anyhow::Result;
+use diesel::{
+    connection::{AnsiTransactionManager, SimpleConnection, TransactionManager},
+    dsl::sql,
+    prelude::*,
+    sql_types::{BigInt, Integer, Text},
+};
+use tracing::warn;
+
+use crate::{
+    db::ConnectionPool,
+    models::StoredObject,
+    schema::{basic_output_features, expiration_unlock_conditions, objects},
+};
+
+/// The tables whose secondary indexes are built after the rows are inserted.
+const LOADED_TABLES: &str = "'objects', 'expiration_unlock_conditions', 'basic_output_features'";
+
+/// Rows per insert statement, staying below the SQLite limit of 32766 bound
+/// parameters.
+const ROWS_PER_STATEMENT: usize = 1000;
+
+/// Page cache used during the load, in KiB.
+const BULK_LOAD_CACHE_SIZE_KIB: i64 = 256 * 1024;
+
+/// Insert the objects, their expiration unlock conditions and their features
+/// if the database is empty, returning whether it was.
+///
+/// The durability and foreign key checks of the connection are relaxed during
+/// the load, which is safe since it is all or nothing.
+#[tracing::instrument(skip_all, fields(count = stored_objects.len()), err)]
+pub(crate) fn bulk_load(pool: &ConnectionPool, stored_objects: &[StoredObject]) -> Result<bool> {
+    let mut conn = pool.get_connection()?;
+
+    let synchronous = diesel::select(sql::<Integer>(
+        "(SELECT synchronous FROM pragma_synchronous())",
+    ))
+    .get_result::<i32>(&mut conn)?;
+    let cache_size = diesel::select(sql::<BigInt>(
+        "(SELECT cache_size FROM pragma_cache_size())",
+    ))
+    .get_result::<i64>(&mut conn)?;
+    let foreign_keys = diesel::select(sql::<Integer>(
+        "(SELECT foreign_keys FROM pragma_foreign_keys())",
+    ))
+    .get_result::<i32>(&mut conn)?;
+
+    // These pragmas have no effect within a transaction
+    conn.batch_execute(&format!(
+        "PRAGMA synchronous = OFF; PRAGMA foreign_keys = OFF; PRAGMA cache_size = -{BULK_LOAD_CACHE_SIZE_KIB};"
+    ))?;
+    let loaded = conn.immediate_transaction(|conn| load(conn, stored_objects));
+    // Switch back to the settings of the pool, even if the load failed
+    let restored = conn.batch_execute(&format!(
+        "PRAGMA synchronous = {synchronous}; PRAGMA foreign_keys = {foreign_keys}; PRAGMA cache_size = {cache_size};"
+    ));
+    let Err(restore_error) = restored else {
+        return loaded;
+    };
+
+    // The pool must not hand out a connection with relaxed durability and
+    // foreign key checks
+    mark_broken(&mut conn);
+    match loaded {
+        Ok(loaded) => {
+            warn!(
+                "discarding the bulk load connection, failed to restore its pragmas: {restore_error}"
+            );
+            Ok(loaded)
+        }
+        Err(e) => Err(e.context(format!(
+            "failed to restore the pragmas of the connection: {restore_error}"
+        ))),
+    }
+}
+
+/// Mark the connection as broken so the pool closes it instead of taking it
+/// back.
+fn mark_broken(conn: &mut SqliteConnection) {
+    AnsiTransactionManager::transaction_manager_status_mut(conn).set_in_error();
+}
+
+fn load(conn: &mut SqliteConnection, stored_objects: &[StoredObject]) -> Result<bool> {
+    let stored = objects::table
+        .select(objects::id)
+        .first::<Vec<u8>>(conn)
+        .optional()?;
+    if stored.is_some() {
+        return Ok(false);
+    }
+
+    // Rebuilt from their own definition, the implicit indexes of the primary
+    // keys and unique constraints having none
+    let indexes = diesel::sql_query(format!(
+        "SELECT name, sql FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL AND tbl_name IN ({LOADED_TABLES})"
+    ))
+    .load::<Index>(conn)?;
+    for index in &indexes {
+        conn.batch_execute(&format!("DROP INDEX \"{}\";", index.name))?;
+    }
+
+    for chunk in stored_objects.chunks(ROWS_PER_STATEMENT) {
+        diesel::insert_into(objects::table)
+            .values(chunk)
+            .execute(conn)?;
+
+        let conditions = chunk
+            .iter()
+            .filter_map(|stored_object| stored_object.expiration_unlock_condition().transpose())
+            .collect::<Result<Vec<_>>>()?;
+        if !conditions.is_empty() {
+            diesel::insert_into(expiration_unlock_conditions::table)
+                .values(&conditions)
+                .execute(conn)?;
+        }
+
+        let features = chunk
+            .iter()
+            .filter_map(|stored_object| stored_object.basic_output_features().transpose())
+            .collect::<Result<Vec<_>>>()?;
+        if !features.is_empty() {
+            diesel::insert_into(basic_output_features::table)
+                .values(&features)
+                .execute(conn)?;
+        }
+    }
+
+    for index in &indexes {
+        conn.batch_execute(&index.sql)?;
+    }
+
+    Ok(true)
+}
+
+#[derive(QueryableByName)]
+struct Index {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    sql: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc, time::Instant};
+
+    use super::*;
+    use crate::{
+        db::{ConnectionPoolConfig, Name},
+        metrics::{METRICS, Metrics},
+        rest::routes::test_utils::create_basic_output,
+        sync::worker::{CheckpointChanges, write_checkpoints},
+    };
+
+    fn genesis_objects(count: usize) -> Vec<StoredObject> {
+        (0..count)
+            .map(|i| {
+                let owner = iota_types::base_types::IotaAddress::random_for_testing_only();
+                StoredObject::new_basic_for_testing(create_basic_output(owner, 1, i as u32))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn new_pool(test_db: &str) -> ConnectionPool {
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        pool
+    }
+
+    fn index_definitions(conn: &mut SqliteConnection) -> Vec<(String, String)> {
+        diesel::sql_query(format!(
+            "SELECT name, sql FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL AND tbl_name IN ({LOADED_TABLES}) ORDER BY name"
+        ))
+        .load::<Index>(conn)
+        .unwrap()
+        .into_iter()
+        .map(|index| (index.name, index.sql))
+        .collect()
+    }
+
+    #[test]
+    fn bulk_load_into_empty_database() {
+        let test_db = "bulk_load_into_empty_database.db";
+        let pool = new_pool(test_db);
+        let mut stored_objects = genesis_objects(ROWS_PER_STATEMENT + 1);
+        // Outputs without expiration unlock condition are loaded too, with their
+        // features
+        let owner = iota_types::base_types::IotaAddress::random_for_testing_only();
+        stored_objects.push(
+            StoredObject::new_basic_for_testing(iota_types::stardust::output::basic::BasicOutput {
+                expiration: None,
+                tag: Some(b"deposit".to_vec()),
+                ..create_basic_output(owner, 1, 0)
+            })
+            .unwrap(),
+        );
+
+        let mut conn = pool.get_connection().unwrap();
+        let indexes = index_definitions(&mut conn);
+        assert!(indexes.iter().any(|(name, _)| name == "euc_owner"));
+        drop(conn);
+
+        assert!(bulk_load(&pool, &stored_objects).unwrap());
+
+        let mut conn = pool.get_connection().unwrap();
+        let count: i64 = expiration_unlock_conditions::table
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(count as usize, stored_objects.len() - 1);
+        let count: i64 = basic_output_features::table
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(index_definitions(&mut conn), indexes);
+        let foreign_keys = diesel::select(sql::<Integer>(
+            "(SELECT foreign_keys FROM pragma_foreign_keys())",
+        ))
+        .get_result::<i32>(&mut conn)
+        .unwrap();
+        assert_eq!(foreign_keys, 1);
+
+        // Only an empty database is bulk loaded
+        assert!(!bulk_load(&pool, &genesis_objects(1)).unwrap());
+        let count: i64 = objects::table.count().get_result(&mut conn).unwrap();
+        assert_eq!(count as usize, stored_objects.len());
+
+        drop(conn);
+        std::fs::remove_file(test_db).unwrap();
+    }
+
+    #[test]
+    fn broken_connection_is_not_reused() {
+        let test_db = "broken_connection_is_not_reused.db";
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+        let mut config = ConnectionPoolConfig::default();
+        config.set_pool_size(1);
+        let pool = ConnectionPool::new_with_url(test_db, config, Name::Objects).unwrap();
+        let foreign_keys = |conn: &mut SqliteConnection| {
+            diesel::select(sql::<Integer>(
+                "(SELECT foreign_keys FROM pragma_foreign_keys())",
+            ))
+            .get_result::<i32>(conn)
+            .unwrap()
+        };
+
+        let mut conn = pool.get_connection().unwrap();
+        conn.batch_execute("PRAGMA foreign_keys = OFF;").unwrap();
+        mark_broken(&mut conn);
+        drop(conn);
+
+        // A new connection, configured by the pool, replaces the broken one
+        let mut conn = pool.get_connection().unwrap();
+        assert_eq!(foreign_keys(&mut conn), 1);
+
+        drop(conn);
+        std::fs::remove_file(test_db).unwrap();
+    }
+
+    /// Compare the bulk load with the genesis ingestion it replaced, writing
+    /// each object in its own transaction, run with `cargo test --release
+    /// bench_genesis_ingestion -- --ignored`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_genesis_ingestion() {
+        const OBJECTS: usize = 100_000;
+        METRICS.get_or_init(|| Arc::new(Metrics::new(&prometheus::Registry::new())));
+        let stored_objects = genesis_objects(OBJECTS);
+        let per_object_changes = stored_objects
+            .iter()
+            .map(|stored_object| {
+                vec![CheckpointChanges {
+                    sequence_number: 0,
+                    created_objects: vec![stored_object.clone()],
+                    deleted_addresses: vec![],
+                }]
+            })
+            .collect::<Vec<_>>();
+
+        let test_db = "bench_genesis_ingestion_per_object.db";
+        let pool = new_pool(test_db);
+        let start = Instant::now();
+        for changes in per_object_changes {
+            write_checkpoints(&pool, changes).unwrap();
+        }
+        let per_object = start.elapsed();
+        drop(pool);
+        std::fs::remove_file(test_db).unwrap();
+
+        let test_db = "bench_genesis_ingestion_bulk.db";
+        let pool = new_pool(test_db);
+        let start = Instant::now();
+        assert!(bulk_load(&pool, &stored_objects).unwrap());
+        let bulk = start.elapsed();
+        drop(pool);
+        std::fs::remove_file(test_db).unwrap();
+
+        assert!(
+            bulk < per_object,
+            "bulk load of {OBJECTS} objects took {bulk:?}, {per_object:?} one by one"
+        );
+    }
+}
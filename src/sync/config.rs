@@ -1,37 +1,89 @@
 // Copyright (c) 2024 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use std::{net::SocketAddr, num::NonZeroUsize, path::PathBuf};
+
 use clap::Args;
 use iota_types::{STARDUST_PACKAGE_ID, base_types::ObjectID};
 use url::Url;
 
+use crate::sync::RetryConfig;
+
 /// Max queue size of checkpoints for the Indexer to process
 const DOWNLOAD_QUEUE_SIZE: usize = 200;
 /// Limit indexing parallelism on big checkpoints to avoid OOM,
 /// by limiting the total size of batch checkpoints to ~20MB
 const CHECKPOINT_PROCESSING_BATCH_DATA_LIMIT: usize = 20000000;
+/// Max time to wait for more checkpoints to fill a write batch
+const WRITE_BATCH_TIMEOUT_MS: u64 = 100;
 
 #[derive(Args, Debug, Clone)]
 pub struct IndexerConfig {
     /// The fullnode gRPC URL used to fetch checkpoint data and synchronize.
     #[arg(long)]
     pub remote_store_url: Url,
-    /// Max queue size of checkpoints for the Indexer to process.
+    /// Max queue size of checkpoints for the Indexer to process, which is also
+    /// the number of checkpoints fetched concurrently.
     #[arg(long, default_value_t = DOWNLOAD_QUEUE_SIZE)]
     #[arg(env = "DOWNLOAD_QUEUE_SIZE")]
     pub download_queue_size: usize,
+    /// Max number of checkpoints processed concurrently, defaults to the
+    /// download queue size. Set to 1 to apply the checkpoints strictly in
+    /// order.
+    #[arg(long, env = "PROCESSING_CONCURRENCY")]
+    pub processing_concurrency: Option<NonZeroUsize>,
+    /// Max number of checkpoints whose changes are written in a single
+    /// database transaction. Batching needs several checkpoints to be
+    /// processed concurrently.
+    #[arg(long, default_value = "1", env = "WRITE_BATCH_SIZE")]
+    pub write_batch_size: NonZeroUsize,
+    /// Max time to wait for more checkpoints to fill a write batch, in
+    /// milliseconds.
+    #[arg(long, default_value_t = WRITE_BATCH_TIMEOUT_MS)]
+    #[arg(env = "WRITE_BATCH_TIMEOUT_MS")]
+    pub write_batch_timeout_ms: u64,
     /// Limit indexing parallelism on big checkpoints to avoid OOM,
     /// by limiting the total size of batch checkpoints to ~20MB.
     #[arg(long, default_value_t = CHECKPOINT_PROCESSING_BATCH_DATA_LIMIT)]
     #[arg(env = "CHECKPOINT_PROCESSING_BATCH_DATA_LIMIT")]
     pub checkpoint_processing_batch_data_limit: usize,
+    #[clap(flatten)]
+    pub retry: RetryConfig,
     /// Reset the current database
     #[arg(long)]
     pub reset_db: bool,
+    /// Bootstrap the databases from a snapshot directory produced by the
+    /// `backup` command, ignored if the Indexer already synced.
+    #[arg(long, env = "BOOTSTRAP_FROM")]
+    pub bootstrap_from: Option<PathBuf>,
     /// Instruct the Indexer to store Objects generated by these package IDs.
     #[arg(short, long, default_value_t = STARDUST_PACKAGE_ID)]
     pub package_id: ObjectID,
     /// The address where the Prometheus metrics will be exposed.
     #[clap(long, default_value = "0.0.0.0:3001", env = "METRICS_SOCKET_ADDRESS")]
-    pub metrics_address: std::net::SocketAddr,
+    pub metrics_address: SocketAddr,
+    /// The address where the admin API will be exposed, disabled if not set.
+    #[clap(long, env = "ADMIN_SOCKET_ADDRESS")]
+    pub admin_address: Option<SocketAddr>,
+    /// Token the admin API clients must pass as `Authorization: Bearer
+    /// <token>`, required along with the admin address.
+    #[clap(
+        long,
+        env = "ADMIN_TOKEN",
+        hide_env_values = true,
+        hide_default_value = true
+    )]
+    pub admin_token: Option<String>,
+    /// The directory where backups triggered through the admin API are
+    /// written.
+    #[clap(long, default_value = "backups", env = "BACKUP_DIR")]
+    pub backup_dir: PathBuf,
+}
+
+impl IndexerConfig {
+    /// The number of checkpoints processed concurrently.
+    pub fn processing_concurrency(&self) -> usize {
+        self.processing_concurrency
+            .map_or(self.download_queue_size, NonZeroUsize::get)
+    }
 }
@@ -0,0 +1,138 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runtime control of the checkpoint syncing, driven by the admin API.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use iota_types::messages_checkpoint::CheckpointSequenceNumber;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+/// Lets the operators pause, resume and restart the checkpoint syncing.
+#[derive(Clone, Debug)]
+pub(crate) struct SyncControl {
+    paused: Arc<watch::Sender<bool>>,
+    /// The number of requests to skip the objects the syncing halted on.
+    skip_requests: Arc<AtomicU64>,
+    /// The checkpoint to restart the syncing from, if requested.
+    reindex_from: Arc<watch::Sender<Option<CheckpointSequenceNumber>>>,
+}
+
+impl Default for SyncControl {
+    fn default() -> Self {
+        Self {
+            paused: Arc::new(watch::Sender::new(false)),
+            skip_requests: Default::default(),
+            reindex_from: Arc::new(watch::Sender::new(None)),
+        }
+    }
+}
+
+impl SyncControl {
+    /// Pause the processing of the checkpoints, returning whether it was
+    /// running.
+    pub(crate) fn pause(&self) -> bool {
+        !self.paused.send_replace(true)
+    }
+
+    /// Resume the processing of the checkpoints, returning whether it was
+    /// paused.
+    pub(crate) fn resume(&self) -> bool {
+        self.paused.send_replace(false)
+    }
+
+    /// Resume the processing of the checkpoints, skipping the objects that
+    /// could not be indexed in the ones it halted on, returning whether it was
+    /// paused.
+    pub(crate) fn skip_bad_objects(&self) -> bool {
+        self.skip_requests.fetch_add(1, Ordering::SeqCst);
+        self.resume()
+    }
+
+    /// The number of requests to skip the objects the syncing halted on, the
+    /// checkpoints halted before the latest one being skipped.
+    pub(crate) fn skip_requests(&self) -> u64 {
+        self.skip_requests.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the processing is resumed, failing if cancelled meanwhile
+    /// so that the checkpoint is not recorded as processed.
+    pub(crate) async fn wait_until_resumed(
+        &self,
+        cancel_token: &CancellationToken,
+    ) -> anyhow::Result<()> {
+        let mut paused = self.paused.subscribe();
+        tokio::select! {
+            _ = paused.wait_for(|paused| !paused) => Ok(()),
+            _ = cancel_token.cancelled() => anyhow::bail!("syncing stopped while paused"),
+        }
+    }
+
+    /// Request restarting the syncing from the given checkpoint.
+    pub(crate) fn request_reindex(&self, from: CheckpointSequenceNumber) {
+        self.reindex_from.send_replace(Some(from));
+    }
+
+    /// Wait for a reindex request and consume it.
+    pub(crate) async fn reindex_requested(&self) -> CheckpointSequenceNumber {
+        let mut reindex_from = self.reindex_from.subscribe();
+        loop {
+            // The sender is owned by `self`, so it cannot be closed
+            _ = reindex_from.wait_for(Option::is_some).await;
+            if let Some(from) = self.reindex_from.send_replace(None) {
+                return from;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pause_and_reindex() {
+        let control = SyncControl::default();
+        let cancel_token = CancellationToken::new();
+        control.wait_until_resumed(&cancel_token).await.unwrap();
+
+        assert!(control.pause());
+        assert!(!control.pause());
+        let waiting = tokio::spawn({
+            let control = control.clone();
+            let cancel_token = cancel_token.clone();
+            async move { control.wait_until_resumed(&cancel_token).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        assert!(control.resume());
+        waiting.await.unwrap().unwrap();
+
+        control.pause();
+        cancel_token.cancel();
+        assert!(control.wait_until_resumed(&cancel_token).await.is_err());
+
+        control.pause();
+        let skips = control.skip_requests();
+        assert!(control.skip_bad_objects());
+        assert!(control.skip_requests() > skips);
+        // Resumed
+        assert!(control.pause());
+
+        // The latest request wins
+        control.request_reindex(10);
+        control.request_reindex(5);
+        assert_eq!(control.reindex_requested().await, 5);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), control.reindex_requested())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Retries of the failing checkpoints, and record of the failures in the
+//! progress store.
+//!
+//! A checkpoint failing to be processed is retried with an exponential
+//! backoff, and the syncing is halted once the retries are exhausted. It can
+//! then be resumed through the admin API. Objects that cannot be indexed
+//! either halt the syncing right away or are skipped, depending on the
+//! policy. The ones it halted on can be skipped through the admin API.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use diesel::prelude::*;
+use iota_types::messages_checkpoint::CheckpointSequenceNumber;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::ConnectionPool,
+    models::{CheckpointFailure, NewCheckpointFailure},
+    schema::checkpoint_failures,
+    sync::unix_timestamp_ms,
+};
+
+/// What to do with the objects that cannot be indexed.
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadObjectPolicy {
+    /// Halt the syncing until the object is skipped through the admin API.
+    #[default]
+    Halt,
+    /// Skip the object, recording it as a failure.
+    Skip,
+}
+
+impl std::fmt::Display for BadObjectPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Halt => f.write_str("halt"),
+            Self::Skip => f.write_str("skip"),
+        }
+    }
+}
+
+/// Settings of the handling of the failing checkpoints.
+#[derive(Args, Debug, Clone)]
+pub struct RetryConfig {
+    /// Max number of retries of a failing checkpoint before halting the
+    /// syncing.
+    #[arg(long, default_value_t = 5, env = "CHECKPOINT_MAX_RETRIES")]
+    pub checkpoint_max_retries: u32,
+    /// Delay before the first retry of a failing checkpoint, in milliseconds.
+    /// It doubles on each retry.
+    #[arg(long, default_value_t = 500, env = "CHECKPOINT_RETRY_BACKOFF_MS")]
+    pub checkpoint_retry_backoff_ms: u64,
+    /// Max delay between the retries of a failing checkpoint, in milliseconds.
+    #[arg(
+        long,
+        default_value_t = 30_000,
+        env = "CHECKPOINT_RETRY_MAX_BACKOFF_MS"
+    )]
+    pub checkpoint_retry_max_backoff_ms: u64,
+    /// What to do with the objects that cannot be indexed, like outputs that
+    /// cannot be decoded.
+    #[arg(long, value_enum, default_value_t = BadObjectPolicy::Halt, env = "BAD_OBJECT_POLICY")]
+    pub bad_object_policy: BadObjectPolicy,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_max_retries: 5,
+            checkpoint_retry_backoff_ms: 500,
+            checkpoint_retry_max_backoff_ms: 30_000,
+            bad_object_policy: Default::default(),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The delay before the given retry, starting from 1.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_millis(
+            self.checkpoint_retry_backoff_ms
+                .saturating_mul(factor)
+                .min(self.checkpoint_retry_max_backoff_ms),
+        )
+    }
+}
+
+/// An object of a checkpoint that cannot be indexed.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("object {object_id} of transaction {transaction_digest} cannot be indexed: {error}")]
+pub(crate) struct BadObject {
+    pub(crate) transaction_digest: String,
+    pub(crate) object_id: String,
+    pub(crate) error: String,
+}
+
+/// What was done about a failure.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum FailureAction {
+    Skipped,
+    Halted,
+}
+
+impl FailureAction {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Skipped => "skipped",
+            Self::Halted => "halted",
+        }
+    }
+}
+
+/// Records the failures in the progress store.
+#[derive(Clone, Debug)]
+pub(crate) struct FailureStore {
+    pool: ConnectionPool,
+}
+
+impl FailureStore {
+    pub(crate) fn new(pool: ConnectionPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record the failure of a checkpoint, or of one of its objects.
+    pub(crate) fn record(
+        &self,
+        sequence_number: CheckpointSequenceNumber,
+        error: &anyhow::Error,
+        action: FailureAction,
+    ) -> Result<()> {
+        let bad_object = error.downcast_ref::<BadObject>();
+        let failure = NewCheckpointFailure {
+            checkpoint_sequence_number: sequence_number as i64,
+            transaction_digest: bad_object.map(|bad| bad.transaction_digest.clone()),
+            object_id: bad_object.map(|bad| bad.object_id.clone()),
+            error: format!("{error:#}"),
+            action: action.label().to_owned(),
+            recorded_at_ms: unix_timestamp_ms() as i64,
+        };
+
+        diesel::insert_into(checkpoint_failures::table)
+            .values(&failure)
+            .execute(&mut self.pool.get_connection()?)?;
+        Ok(())
+    }
+
+    /// Get the latest failures, most recent first.
+    pub(crate) fn latest(&self, limit: i64) -> Result<Vec<CheckpointFailure>> {
+        Ok(checkpoint_failures::table
+            .select(CheckpointFailure::as_select())
+            .order_by(checkpoint_failures::id.desc())
+            .limit(limit)
+            .load(&mut self.pool.get_connection()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::db::Name;
+
+    #[test]
+    fn exponential_backoff() {
+        let config = RetryConfig {
+            checkpoint_retry_backoff_ms: 100,
+            checkpoint_retry_max_backoff_ms: 1_000,
+            ..Default::default()
+        };
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(4), Duration::from_millis(800));
+        assert_eq!(config.backoff(5), Duration::from_millis(1_000));
+        assert_eq!(config.backoff(100), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn record_failures() {
+        let test_db = "record_failures.db";
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::ProgressStore).unwrap();
+        pool.run_migrations().unwrap();
+        let store = FailureStore::new(pool);
+
+        store
+            .record(
+                7,
+                &anyhow::anyhow!("database is locked"),
+                FailureAction::Halted,
+            )
+            .unwrap();
+        let bad_object = BadObject {
+            transaction_digest: "digest".to_owned(),
+            object_id: "0x1".to_owned(),
+            error: "expiration unlock condition does not exists".to_owned(),
+        };
+        store
+            .record(8, &bad_object.into(), FailureAction::Skipped)
+            .unwrap();
+
+        let failures = store.latest(10).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].checkpoint_sequence_number, 8);
+        assert_eq!(failures[0].transaction_digest.as_deref(), Some("digest"));
+        assert_eq!(failures[0].object_id.as_deref(), Some("0x1"));
+        assert_eq!(failures[0].action, "skipped");
+        assert_eq!(failures[1].object_id, None);
+        assert_eq!(failures[1].action, "halted");
+
+        std::fs::remove_file(test_db).unwrap();
+    }
+}
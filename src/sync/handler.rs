@@ -3,19 +3,61 @@
 
 //! Checkpoint syncing Handlers for the Indexer
 
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
 use iota_data_ingestion_core::{
-    DataIngestionMetrics, IndexerExecutor, ReaderOptions, WorkerPool,
+    DataIngestionMetrics, IndexerExecutor, ProgressStore, ReaderOptions, WorkerPool,
     reader::v2::{CheckpointReaderConfig, RemoteUrl},
 };
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
+use tracing::info;
 
 use crate::{
+    admin::{AdminState, spawn_admin_server},
+    config::ConfigFile,
     db::ConnectionPool,
-    metrics::spawn_prometheus_server,
-    sync::{IndexerConfig, progress_store::SqliteProgressStore, worker::CheckpointWorker},
+    metrics::{spawn_database_metrics_updater, spawn_prometheus_server},
+    rest::RESPONSE_CACHE,
+    sync::{
+        FailureStore, IndexerConfig, SyncControl,
+        batch::WriteBatcher,
+        progress_store::SqliteProgressStore,
+        reset_watermark,
+        worker::{
+            CheckpointWorker, INDEXED_PACKAGE_IDS, LAST_PROGRESS_UNIX_TIMESTAMP_MS,
+            unix_timestamp_ms,
+        },
+    },
 };
 
+/// Name of the task under which the `CheckpointWorker` progress is recorded.
+pub const WORKER_TASK_NAME: &str = "primary";
+
+/// Whether the `IndexerExecutor` task is running.
+pub static INDEXER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Flags the `IndexerExecutor` task as running until dropped, including on
+/// panic or abort.
+struct RunningGuard;
+
+impl RunningGuard {
+    fn new() -> Self {
+        INDEXER_RUNNING.store(true, Ordering::SeqCst);
+        LAST_PROGRESS_UNIX_TIMESTAMP_MS.store(unix_timestamp_ms(), Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        INDEXER_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
 /// The `Indexer` encapsulates the main logic behind the checkpoint
 /// synchronization from a Fullnode.
 ///
@@ -34,6 +76,7 @@ impl Indexer {
         pool: ConnectionPool,
         pool_progress_store: ConnectionPool,
         indexer_config: Box<IndexerConfig>,
+        effective_config: ConfigFile,
     ) -> Result<Self, anyhow::Error> {
         // Set up the Prometheus metrics service
         let cancel_token = CancellationToken::new();
@@ -45,46 +88,36 @@ impl Indexer {
             &mut tasks,
         )?;
 
-        // The IndexerExecutor handles the Sync and Fetch of checkpoints from a Fullnode
-        let mut executor = IndexerExecutor::new(
-            // Read from sqlite file the latest synced checkpoint and start fetching the next
-            // checkpoint
-            SqliteProgressStore::new(pool_progress_store),
-            // Based on how many workers do we have we may increase this value, what it does under
-            // the hood is to calculate the channel capacity by this formula `number_of_jobs *
-            // MAX_CHECKPOINTS_IN_PROGRESS`, where MAX_CHECKPOINTS_IN_PROGRESS = 10000
-            1,
-            DataIngestionMetrics::new(&registry),
-            cancel_token.clone(),
-        );
+        spawn_database_metrics_updater(pool.clone(), cancel_token.clone(), &mut tasks);
 
-        // Register the CheckpointWorker which will handle the CheckpointData once
-        // fetched by the CheckpointReader
-        let worker = WorkerPool::new(
-            CheckpointWorker::new(pool, indexer_config.package_id),
-            "primary".to_owned(),
-            indexer_config.download_queue_size,
-            Default::default(),
-        );
-        executor.register(worker).await?;
+        let control = SyncControl::default();
+        if let Some(admin_address) = indexer_config.admin_address {
+            spawn_admin_server(
+                admin_address,
+                AdminState::new(
+                    pool.clone(),
+                    pool_progress_store.clone(),
+                    indexer_config.backup_dir.clone(),
+                    control.clone(),
+                    effective_config,
+                ),
+                indexer_config.admin_token.clone(),
+                cancel_token.clone(),
+                &mut tasks,
+            )?;
+        }
+
+        INDEXED_PACKAGE_IDS.get_or_init(|| vec![indexer_config.package_id]);
 
         // Run the IndexerExecutor in a separate task
-        tasks.spawn(async move {
-            executor
-                .run_with_config(CheckpointReaderConfig {
-                    remote_store_url: Some(RemoteUrl::Fullnode(
-                        indexer_config.remote_store_url.into(),
-                    )),
-                    reader_options: ReaderOptions {
-                        batch_size: indexer_config.download_queue_size,
-                        data_limit: indexer_config.checkpoint_processing_batch_data_limit,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                })
-                .await?;
-            Ok(())
-        });
+        tasks.spawn(run_executor(
+            pool,
+            pool_progress_store,
+            indexer_config,
+            DataIngestionMetrics::new(&registry),
+            control,
+            cancel_token.clone(),
+        ));
 
         Ok(Self {
             tasks,
@@ -110,3 +143,96 @@ impl Indexer {
         Ok(())
     }
 }
+
+/// Run the `IndexerExecutor` until cancelled, restarting it from another
+/// checkpoint whenever a reindex is requested.
+async fn run_executor(
+    pool: ConnectionPool,
+    pool_progress_store: ConnectionPool,
+    indexer_config: Box<IndexerConfig>,
+    metrics: DataIngestionMetrics,
+    control: SyncControl,
+    cancel_token: CancellationToken,
+) -> anyhow::Result<()> {
+    let _running = RunningGuard::new();
+    loop {
+        let run_token = cancel_token.child_token();
+
+        // The IndexerExecutor handles the Sync and Fetch of checkpoints from a Fullnode
+        let mut executor = IndexerExecutor::new(
+            // Read from sqlite file the latest synced checkpoint and start fetching the next
+            // checkpoint
+            SqliteProgressStore::new(pool_progress_store.clone()),
+            // The number of worker pools registered below, which only sizes the channel they
+            // report their progress through, as `number_of_jobs * MAX_CHECKPOINTS_IN_PROGRESS`
+            // where MAX_CHECKPOINTS_IN_PROGRESS = 10000. The checkpoints fetched concurrently
+            // are bounded by the `download_queue_size` instead, and the ones processed
+            // concurrently by the `processing_concurrency` of the worker pool
+            1,
+            metrics.clone(),
+            run_token.clone(),
+        );
+
+        let batcher = (indexer_config.write_batch_size.get() > 1).then(|| {
+            WriteBatcher::spawn(
+                pool.clone(),
+                indexer_config.write_batch_size,
+                Duration::from_millis(indexer_config.write_batch_timeout_ms),
+            )
+        });
+
+        // Register the CheckpointWorker which will handle the CheckpointData once
+        // fetched by the CheckpointReader
+        let worker = WorkerPool::new(
+            CheckpointWorker::new(
+                pool.clone(),
+                indexer_config.package_id,
+                batcher,
+                control.clone(),
+                run_token.clone(),
+                indexer_config.retry.clone(),
+                FailureStore::new(pool_progress_store.clone()),
+            ),
+            WORKER_TASK_NAME.to_owned(),
+            indexer_config.processing_concurrency(),
+            Default::default(),
+        );
+        executor.register(worker).await?;
+
+        let run = executor.run_with_config(CheckpointReaderConfig {
+            remote_store_url: Some(RemoteUrl::Fullnode(
+                indexer_config.remote_store_url.to_string(),
+            )),
+            reader_options: ReaderOptions {
+                batch_size: indexer_config.download_queue_size,
+                data_limit: indexer_config.checkpoint_processing_batch_data_limit,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        tokio::pin!(run);
+
+        let from = tokio::select! {
+            result = &mut run => {
+                result?;
+                return Ok(());
+            }
+            from = control.reindex_requested() => from,
+        };
+
+        // Stop the executor before moving the watermark, so that it does not
+        // record its progress over it
+        run_token.cancel();
+        run.await?;
+        SqliteProgressStore::new(pool_progress_store.clone())
+            .save(WORKER_TASK_NAME.to_owned(), from)
+            .await?;
+        // The responses cached and stamped so far reflect later checkpoints
+        // than the ones about to be indexed
+        if let Some(cache) = RESPONSE_CACHE.get() {
+            cache.clear();
+        }
+        reset_watermark();
+        info!("Reindexing from checkpoint {from}");
+    }
+}
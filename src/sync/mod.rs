@@ -3,11 +3,23 @@
 
 //! Checkpoint syncing Handlers for the Indexer
 
+mod batch;
+mod bulk_load;
 mod config;
+mod control;
+mod failures;
 mod handler;
 mod progress_store;
 mod worker;
 
 pub use config::IndexerConfig;
-pub use handler::Indexer;
-pub use worker::LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS;
+pub(crate) use control::SyncControl;
+pub(crate) use failures::FailureStore;
+pub use failures::{BadObjectPolicy, RetryConfig};
+pub use handler::{INDEXER_RUNNING, Indexer, WORKER_TASK_NAME};
+pub use progress_store::SqliteProgressStore;
+pub use worker::{
+    INDEXED_PACKAGE_IDS, LAST_PROGRESS_UNIX_TIMESTAMP_MS, LATEST_CHECKPOINT_SEQUENCE_NUMBER,
+    LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS, WATERMARK_EPOCH,
+};
+pub(crate) use worker::{latest_checkpoint_timestamp_ms, reset_watermark, unix_timestamp_ms};
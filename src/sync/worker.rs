@@ -5,28 +5,109 @@
 //! can apply filtering logic to store only the desired data if necessary into a
 //! local or remote storage
 
-use std::sync::{Arc, OnceLock, atomic::AtomicU64};
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use axum::async_trait;
-use diesel::{Connection, ExpressionMethods, RunQueryDsl, insert_into};
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection, insert_into};
 use iota_data_ingestion_core::Worker;
 use iota_types::{
     base_types::ObjectID,
     full_checkpoint_content::{CheckpointData, CheckpointTransaction},
-    transaction::{InputObjectKind, TransactionDataAPI},
+    messages_checkpoint::CheckpointSequenceNumber,
+    object::Object,
+    transaction::TransactionDataAPI,
 };
+use prometheus::HistogramTimer;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
 use crate::{
     db::ConnectionPool,
-    metrics::METRICS,
-    models::{ExpirationUnlockCondition, IotaAddress, ObjectType, StoredObject},
-    schema::{expiration_unlock_conditions::dsl::*, objects::dsl::*},
+    metrics::{METRICS, update_checkpoint_timestamp_lag},
+    models::{IotaAddress, ObjectType, Rejection, StoredObject},
+    rest::RESPONSE_CACHE,
+    schema::{basic_output_features, expiration_unlock_conditions::dsl::*, objects::dsl::*},
+    sync::{
+        SyncControl,
+        batch::WriteBatcher,
+        bulk_load::bulk_load,
+        failures::{BadObject, BadObjectPolicy, FailureAction, FailureStore, RetryConfig},
+    },
 };
 
 /// Stores the latest checkpoint unix timestamp in milliseconds processed by the
 /// `CheckpointWorker`.
 pub static LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS: OnceLock<AtomicU64> = OnceLock::new();
 
+/// Stores the latest checkpoint sequence number processed by the
+/// `CheckpointWorker`.
+pub static LATEST_CHECKPOINT_SEQUENCE_NUMBER: OnceLock<AtomicU64> = OnceLock::new();
+
+/// Max number of object IDs bound in a single query, staying below the SQLite
+/// limit of 32766 bound parameters.
+const IDS_PER_QUERY: usize = 10_000;
+
+/// Stores the IDs of the packages whose objects are indexed.
+pub static INDEXED_PACKAGE_IDS: OnceLock<Vec<ObjectID>> = OnceLock::new();
+
+/// Stores the wall-clock unix timestamp in milliseconds at which the
+/// `CheckpointWorker` last indexed a checkpoint, or at which the syncing
+/// started, zero if it never did.
+pub static LAST_PROGRESS_UNIX_TIMESTAMP_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Publish the latest indexed checkpoint, unless a later one already was by a
+/// concurrently processed checkpoint.
+fn publish_watermark(sequence_number: CheckpointSequenceNumber, timestamp_ms: u64) {
+    LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS
+        .get_or_init(|| AtomicU64::new(0))
+        .fetch_max(timestamp_ms, Ordering::SeqCst);
+    LATEST_CHECKPOINT_SEQUENCE_NUMBER
+        .get_or_init(|| AtomicU64::new(0))
+        .fetch_max(sequence_number, Ordering::SeqCst);
+}
+
+/// Counts the resets of the watermark, so that the responses stamped before a
+/// reindex are told apart from the ones stamped at the same checkpoint after.
+pub static WATERMARK_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Forget the latest indexed checkpoint when reindexing from an earlier one,
+/// which is published again once indexed.
+pub(crate) fn reset_watermark() {
+    WATERMARK_EPOCH.fetch_add(1, Ordering::SeqCst);
+    for watermark in [
+        &LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS,
+        &LATEST_CHECKPOINT_SEQUENCE_NUMBER,
+    ] {
+        if let Some(watermark) = watermark.get() {
+            watermark.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// The latest indexed checkpoint unix timestamp in milliseconds, if any was
+/// since the start or the last reindex.
+pub(crate) fn latest_checkpoint_timestamp_ms() -> Option<u64> {
+    LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS
+        .get()
+        .map(|timestamp| timestamp.load(Ordering::SeqCst))
+        .filter(|timestamp| *timestamp != 0)
+}
+
+/// The current wall-clock unix timestamp in milliseconds.
+pub(crate) fn unix_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 /// The `CheckpointWorker` is responsible for processing the incoming
 /// `CheckpointData` from the `IndexerExecutor`, apply filtering logic if
 /// necessary and save into a SQLite database
@@ -35,90 +116,518 @@ pub(crate) struct CheckpointWorker {
     pool: ConnectionPool,
     /// Store data only related to the following package ids
     package_id: ObjectID,
+    /// Groups the writes of concurrently processed checkpoints, if enabled.
+    batcher: Option<WriteBatcher>,
+    control: SyncControl,
+    /// Stops waiting for the processing to be resumed.
+    cancel_token: CancellationToken,
+    retry: RetryConfig,
+    failures: FailureStore,
 }
 
 impl CheckpointWorker {
-    pub(crate) fn new(pool: ConnectionPool, package_id: ObjectID) -> Self {
-        Self { pool, package_id }
+    pub(crate) fn new(
+        pool: ConnectionPool,
+        package_id: ObjectID,
+        batcher: Option<WriteBatcher>,
+        control: SyncControl,
+        cancel_token: CancellationToken,
+        retry: RetryConfig,
+        failures: FailureStore,
+    ) -> Self {
+        Self {
+            pool,
+            package_id,
+            batcher,
+            control,
+            cancel_token,
+            retry,
+            failures,
+        }
     }
 
-    /// Check if the provided package_id matches the desired one
-    fn package_id_matches(&self, input_obj_kind: &InputObjectKind) -> bool {
-        matches!(input_obj_kind, InputObjectKind::MovePackage(package_id) if *package_id == self.package_id)
+    /// Check if the object is an output defined by the stardust package.
+    fn is_stardust_output(&self, object: &Object) -> bool {
+        object
+            .struct_tag()
+            .is_some_and(|struct_tag| ObjectID::from(struct_tag.address) == self.package_id)
+            && ObjectType::try_from(&**object).is_ok()
+    }
+
+    /// Whether the object may be indexed, i.e. is shared or is an
+    /// address-owned output. The address-owned objects of other types, like
+    /// the coins, are too common to be counted as rejected.
+    fn is_indexing_candidate(&self, object: &Object) -> bool {
+        object.is_shared() || object.is_address_owned() && self.is_stardust_output(object)
     }
 
     /// Check if the `CheckpointTransaction` is a genesis transaction or
-    /// contains the stardust package.
-    fn tx_touches_stardust_objects(
+    /// touches stardust outputs.
+    fn tx_touches_stardust_objects(&self, checkpoint_tx: &CheckpointTransaction) -> bool {
+        checkpoint_tx
+            .transaction
+            .intent_message()
+            .value
+            .is_genesis_tx()
+            || self.touches_stardust_outputs(
+                &checkpoint_tx.input_objects,
+                &checkpoint_tx.output_objects,
+            )
+    }
+
+    /// Check if stardust outputs are among the input or output objects of a
+    /// transaction, which is the case of the transfers of the address-owned
+    /// ones even though they do not call the stardust package.
+    fn touches_stardust_outputs(
         &self,
-        checkpoint_tx: &CheckpointTransaction,
-    ) -> anyhow::Result<bool> {
-        let tx_data = &checkpoint_tx.transaction.intent_message().value;
-
-        Ok(tx_data.is_genesis_tx()
-            || tx_data
-                .input_objects()?
-                .iter()
-                .any(|input_obj_kind| self.package_id_matches(input_obj_kind)))
-    }
-
-    /// This function iterates over `StoredObject` and
-    /// `ExpirationUnlockCondition` pairs, for each pair it creates a database
-    /// transaction, and inserts both the object and its expiration
-    /// condition. If a conflict arises during the insertion, the existing
-    /// record is updated with the new values.
-    fn multi_insert_as_database_transactions(
+        input_objects: &[Object],
+        output_objects: &[Object],
+    ) -> bool {
+        input_objects
+            .iter()
+            .chain(output_objects)
+            .any(|object| self.is_stardust_output(object))
+    }
+
+    /// Collect the changes of a transaction to the stored objects from its
+    /// output objects and the objects it removed.
+    fn collect_changes<'a>(
         &self,
-        stored_objects: Vec<StoredObject>,
+        sequence_number: CheckpointSequenceNumber,
+        transaction_digest: &str,
+        output_objects: &[Object],
+        removed_objects: impl Iterator<Item = &'a Object>,
+        changes: &mut NetChanges,
+        bad_object_policy: BadObjectPolicy,
     ) -> anyhow::Result<()> {
-        let mut pool = self.pool.get_connection()?;
-        for stored_object in stored_objects {
-            let type_ = stored_object.object_type;
-
-            pool.transaction::<_, anyhow::Error, _>(|conn| {
-                insert_into(objects)
-                    .values(&stored_object)
-                    .on_conflict(id)
-                    .do_update()
-                    .set(&stored_object)
-                    .execute(conn)?;
+        let metrics = METRICS.get().expect("metrics global should be initialized");
+        for obj in removed_objects.filter(|obj| self.is_stardust_output(obj)) {
+            changes.delete(IotaAddress::from(obj.id()));
+        }
+
+        for obj in output_objects {
+            if self.is_stardust_output(obj) && !(obj.is_shared() || obj.is_address_owned()) {
+                // No longer indexed, e.g. transferred to an object
+                changes.delete(IotaAddress::from(obj.id()));
+                continue;
+            }
+            if !self.is_indexing_candidate(obj) {
+                continue;
+            }
+            // The outputs that cannot be decoded are handled by the policy,
+            // and dropped from the index if skipped
+            let stored_object = StoredObject::try_from(obj.clone()).and_then(|stored_object| {
+                stored_object.expiration_unlock_condition()?;
+                Ok(stored_object)
+            });
+            let e = match stored_object {
+                Ok(stored_object) => {
+                    changes.create(stored_object);
+                    continue;
+                }
+                Err(e) => e,
+            };
+            if let Some(rejection) = Rejection::of(&e) {
+                metrics
+                    .rejected_objects_count
+                    .with_label_values(&[rejection.label()])
+                    .inc();
+                continue;
+            }
+            let bad_object = BadObject {
+                transaction_digest: transaction_digest.to_owned(),
+                object_id: obj.id().to_string(),
+                error: format!("{e:#}"),
+            };
+            self.handle_bad_object(sequence_number, bad_object, bad_object_policy)?;
+            changes.delete(IotaAddress::from(obj.id()));
+        }
+
+        Ok(())
+    }
 
-                let eu = ExpirationUnlockCondition::try_from(stored_object)?;
+    /// Get the addresses the stored objects refer to, either as their owner or
+    /// in their expiration unlock condition.
+    fn stored_addresses(
+        &self,
+        object_ids: &[IotaAddress],
+    ) -> anyhow::Result<HashSet<iota_types::base_types::IotaAddress>> {
+        let mut conn = self.pool.get_connection()?;
+        let mut addresses = Vec::new();
+        let mut address_owners = Vec::new();
+        for object_ids in object_ids.chunks(IDS_PER_QUERY) {
+            addresses.extend(
+                expiration_unlock_conditions
+                    .select((owner, return_address))
+                    .filter(object_id.eq_any(object_ids))
+                    .load::<(IotaAddress, IotaAddress)>(&mut conn)?,
+            );
+            address_owners.extend(
+                objects
+                    .select(address_owner)
+                    .filter(id.eq_any(object_ids))
+                    .load::<Option<IotaAddress>>(&mut conn)?,
+            );
+        }
+        Ok(addresses
+            .into_iter()
+            .flat_map(|(owner_address, return_address_)| [owner_address.0, return_address_.0])
+            .chain(
+                address_owners
+                    .into_iter()
+                    .flatten()
+                    .map(|address| address.0),
+            )
+            .collect())
+    }
+}
 
+/// The net changes of the transactions of a checkpoint to the stored objects,
+/// the later changes of an object replacing the earlier ones.
+#[derive(Debug, Default)]
+struct NetChanges {
+    created_objects: Vec<StoredObject>,
+    deleted_addresses: Vec<IotaAddress>,
+    changed: HashSet<IotaAddress>,
+}
+
+impl NetChanges {
+    fn create(&mut self, stored_object: StoredObject) {
+        self.replace(stored_object.id);
+        self.created_objects.push(stored_object);
+    }
+
+    fn delete(&mut self, address: IotaAddress) {
+        self.replace(address);
+        self.deleted_addresses.push(address);
+    }
+
+    /// Drop the earlier changes of the object, if any.
+    fn replace(&mut self, address: IotaAddress) {
+        if !self.changed.insert(address) {
+            self.created_objects
+                .retain(|stored_object| stored_object.id != address);
+            self.deleted_addresses.retain(|deleted| *deleted != address);
+        }
+    }
+}
+
+/// The changes of a checkpoint to the stored objects.
+#[derive(Debug)]
+pub(crate) struct CheckpointChanges {
+    pub(crate) sequence_number: CheckpointSequenceNumber,
+    pub(crate) created_objects: Vec<StoredObject>,
+    pub(crate) deleted_addresses: Vec<IotaAddress>,
+}
+
+/// Apply the changes of the checkpoints in a single database transaction, in
+/// the order of the checkpoints.
+#[tracing::instrument(skip_all, fields(checkpoints = changes.len()), err)]
+pub(crate) fn write_checkpoints(
+    pool: &ConnectionPool,
+    mut changes: Vec<CheckpointChanges>,
+) -> anyhow::Result<()> {
+    changes.sort_by_key(|changes| changes.sequence_number);
+
+    let mut conn = pool.get_connection()?;
+    let deleted_types = conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let mut deleted_types = Vec::new();
+        for changes in &changes {
+            if !changes.created_objects.is_empty() {
+                insert_objects(conn, &changes.created_objects)?;
+            }
+            if !changes.deleted_addresses.is_empty() {
+                deleted_types.extend(delete_objects(conn, &changes.deleted_addresses)?);
+            }
+        }
+        Ok(deleted_types)
+    })?;
+
+    record_writes(
+        changes
+            .iter()
+            .flat_map(|changes| &changes.created_objects)
+            .map(|stored_object| stored_object.object_type),
+        deleted_types,
+    );
+
+    Ok(())
+}
+
+/// Count the written objects by type.
+fn record_writes(
+    inserted_types: impl IntoIterator<Item = ObjectType>,
+    deleted_types: impl IntoIterator<Item = ObjectType>,
+) {
+    let metrics = METRICS.get().expect("global should be initialized");
+    for type_ in inserted_types {
+        match type_ {
+            ObjectType::Basic => metrics.indexed_basic_outputs_count.inc(),
+            ObjectType::Nft => metrics.indexed_nft_outputs_count.inc(),
+        }
+    }
+    for type_ in deleted_types {
+        match type_ {
+            ObjectType::Basic => metrics.deleted_basic_outputs_count.inc(),
+            ObjectType::Nft => metrics.deleted_nft_outputs_count.inc(),
+        }
+    }
+}
+
+/// Insert the objects along with their expiration unlock condition, if any. If
+/// a conflict arises, the existing records are updated with the new values.
+#[tracing::instrument(skip_all, fields(count = stored_objects.len()), err)]
+fn insert_objects(
+    conn: &mut SqliteConnection,
+    stored_objects: &[StoredObject],
+) -> anyhow::Result<()> {
+    let _timer = start_db_write_timer("insert_objects");
+    for stored_object in stored_objects {
+        insert_into(objects)
+            .values(stored_object)
+            .on_conflict(id)
+            .do_update()
+            .set(stored_object)
+            .execute(conn)?;
+
+        match stored_object.expiration_unlock_condition()? {
+            Some(eu) => {
                 insert_into(expiration_unlock_conditions)
                     .values(&eu)
                     .on_conflict(object_id)
                     .do_update()
                     .set(&eu)
                     .execute(conn)?;
+            }
+            None => {
+                diesel::delete(expiration_unlock_conditions)
+                    .filter(object_id.eq(stored_object.id))
+                    .execute(conn)?;
+            }
+        }
+
+        match stored_object.basic_output_features()? {
+            Some(features) => {
+                insert_into(basic_output_features::table)
+                    .values(&features)
+                    .on_conflict(basic_output_features::object_id)
+                    .do_update()
+                    .set(&features)
+                    .execute(conn)?;
+            }
+            None => {
+                diesel::delete(basic_output_features::table)
+                    .filter(basic_output_features::object_id.eq(stored_object.id))
+                    .execute(conn)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the objects, returning the types of the deleted ones.
+#[tracing::instrument(skip_all, fields(count = addresses.len()), err)]
+fn delete_objects(
+    conn: &mut SqliteConnection,
+    addresses: &[IotaAddress],
+) -> anyhow::Result<Vec<ObjectType>> {
+    let _timer = start_db_write_timer("delete_objects");
+    let mut deleted_types = Vec::new();
+    for addresses in addresses.chunks(IDS_PER_QUERY) {
+        deleted_types.extend(
+            diesel::delete(objects)
+                .filter(id.eq_any(addresses))
+                .returning(object_type)
+                .get_results::<ObjectType>(conn)?,
+        );
+    }
+    Ok(deleted_types)
+}
 
-                Ok(())
-            })?;
+fn start_db_write_timer(operation: &str) -> HistogramTimer {
+    METRICS
+        .get()
+        .expect("global should be initialized")
+        .db_write_duration_seconds
+        .with_label_values(&[operation])
+        .start_timer()
+}
+
+impl CheckpointWorker {
+    /// Process a checkpoint once, failing with a [`BadObject`] if an object
+    /// cannot be indexed and the policy is to halt.
+    async fn try_process_checkpoint(
+        &self,
+        checkpoint: &CheckpointData,
+        bad_object_policy: BadObjectPolicy,
+    ) -> anyhow::Result<()> {
+        let sequence_number = checkpoint.checkpoint_summary.sequence_number;
+        let metrics = METRICS.get().expect("metrics global should be initialized");
+        metrics.last_checkpoint_received.set(sequence_number as i64);
+        let _timer = metrics.checkpoint_processing_duration_seconds.start_timer();
 
-            match type_ {
-                ObjectType::Basic => METRICS
-                    .get()
-                    .expect("global should be initialized")
-                    .indexed_basic_outputs_count
-                    .inc(),
-                ObjectType::Nft => METRICS
-                    .get()
-                    .expect("global should be initialized")
-                    .indexed_nft_outputs_count
-                    .inc(),
+        let mut changes = NetChanges::default();
+        for checkpoint_tx in checkpoint.transactions.iter() {
+            metrics.inspected_transactions_count.inc();
+            if self.tx_touches_stardust_objects(checkpoint_tx) {
+                metrics.matched_transactions_count.inc();
+                self.collect_changes(
+                    sequence_number,
+                    &checkpoint_tx.transaction.digest().to_string(),
+                    &checkpoint_tx.output_objects,
+                    checkpoint_tx.removed_objects_pre_version(),
+                    &mut changes,
+                    bad_object_policy,
+                )?;
+            }
+        }
+        let NetChanges {
+            mut created_objects,
+            deleted_addresses,
+            ..
+        } = changes;
+
+        let checkpoint_timestamp = checkpoint.checkpoint_summary.timestamp_ms;
+
+        // Collect the addresses whose cached responses become stale, both the
+        // ones the objects referred to before and after the writes. There is
+        // nothing to look up in an empty cache, like during the genesis, which
+        // is cleared instead so that the queries racing with the writes are not
+        // cached.
+        let stale_addresses = match RESPONSE_CACHE.get() {
+            Some(cache)
+                if cache.is_empty()
+                    && (!created_objects.is_empty() || !deleted_addresses.is_empty()) =>
+            {
+                None
+            }
+            Some(_) if !created_objects.is_empty() || !deleted_addresses.is_empty() => {
+                let object_ids = created_objects
+                    .iter()
+                    .map(|stored_object| stored_object.id)
+                    .chain(deleted_addresses.iter().copied())
+                    .collect::<Vec<_>>();
+                let mut stale_addresses = self.stored_addresses(&object_ids)?;
+                stale_addresses.extend(
+                    created_objects
+                        .iter()
+                        .filter_map(|stored_object| {
+                            stored_object.expiration_unlock_condition().ok().flatten()
+                        })
+                        .flat_map(|eu| [eu.owner.0, eu.return_address.0]),
+                );
+                stale_addresses.extend(
+                    created_objects
+                        .iter()
+                        .filter_map(|stored_object| stored_object.address_owner)
+                        .map(|address| address.0),
+                );
+                Some(stale_addresses)
+            }
+            _ => Some(HashSet::new()),
+        };
+
+        if sequence_number == 0 && deleted_addresses.is_empty() && !created_objects.is_empty() {
+            // The genesis objects are bulk loaded into an empty database
+            let started = std::time::Instant::now();
+            if bulk_load(&self.pool, &created_objects)? {
+                info!(
+                    "Bulk loaded {} genesis objects in {:?}",
+                    created_objects.len(),
+                    started.elapsed()
+                );
+                record_writes(
+                    created_objects
+                        .iter()
+                        .map(|stored_object| stored_object.object_type),
+                    [],
+                );
+                created_objects.clear();
+            }
+        }
+
+        if !created_objects.is_empty() || !deleted_addresses.is_empty() {
+            let changes = CheckpointChanges {
+                sequence_number,
+                created_objects,
+                deleted_addresses,
+            };
+            match &self.batcher {
+                Some(batcher) => batcher.write(changes).await?,
+                None => write_checkpoints(&self.pool, vec![changes])?,
+            }
+        }
+
+        if let Some(cache) = RESPONSE_CACHE.get() {
+            match stale_addresses {
+                Some(stale_addresses) if !stale_addresses.is_empty() => {
+                    cache.invalidate(&stale_addresses)
+                }
+                Some(_) => {}
+                None => cache.clear(),
             }
         }
 
+        // Only published once written, so that the responses are not stamped
+        // with a checkpoint they do not reflect yet
+        publish_watermark(sequence_number, checkpoint_timestamp);
+
+        metrics
+            .last_checkpoint_indexed
+            .set(checkpoint.checkpoint_summary.sequence_number as i64);
+        update_checkpoint_timestamp_lag(metrics, checkpoint_timestamp);
+        LAST_PROGRESS_UNIX_TIMESTAMP_MS.store(unix_timestamp_ms(), Ordering::SeqCst);
+
         Ok(())
     }
 
-    fn delete_objects(&self, addresses: Vec<IotaAddress>) -> anyhow::Result<()> {
-        let mut conn = self.pool.get_connection()?;
-        diesel::delete(objects)
-            .filter(id.eq_any(addresses))
-            .execute(&mut conn)?;
+    /// Skip the object if the policy allows it, recording the failure.
+    fn handle_bad_object(
+        &self,
+        sequence_number: CheckpointSequenceNumber,
+        bad_object: BadObject,
+        bad_object_policy: BadObjectPolicy,
+    ) -> anyhow::Result<()> {
+        if bad_object_policy == BadObjectPolicy::Halt {
+            anyhow::bail!(bad_object);
+        }
+
+        warn!("skipping in checkpoint {sequence_number}: {bad_object}");
+        METRICS
+            .get()
+            .expect("metrics global should be initialized")
+            .skipped_objects_count
+            .inc();
+        let error = bad_object.into();
+        if let Err(e) = self
+            .failures
+            .record(sequence_number, &error, FailureAction::Skipped)
+        {
+            error!("failed to record the skipped object: {e:#}");
+        }
         Ok(())
     }
+
+    /// Record the failure and pause the syncing until resumed through the
+    /// admin API.
+    fn halt(&self, sequence_number: CheckpointSequenceNumber, error: &anyhow::Error) {
+        error!(
+            "halting the syncing at checkpoint {sequence_number}: {error:#}, resume it through the admin API"
+        );
+        if let Err(e) = self
+            .failures
+            .record(sequence_number, error, FailureAction::Halted)
+        {
+            error!("failed to record the failure: {e:#}");
+        }
+        METRICS
+            .get()
+            .expect("metrics global should be initialized")
+            .sync_halted
+            .set(1);
+        self.control.pause();
+    }
 }
 
 #[async_trait]
@@ -126,52 +635,327 @@ impl Worker for CheckpointWorker {
     type Error = anyhow::Error;
     type Message = ();
 
+    #[tracing::instrument(
+        skip_all,
+        fields(sequence_number = checkpoint.checkpoint_summary.sequence_number),
+        err
+    )]
     async fn process_checkpoint(&self, checkpoint: Arc<CheckpointData>) -> anyhow::Result<()> {
-        METRICS
-            .get()
-            .expect("metrics global should be initialized")
-            .last_checkpoint_received
-            .set(checkpoint.checkpoint_summary.sequence_number as i64);
+        let sequence_number = checkpoint.checkpoint_summary.sequence_number;
+        let metrics = METRICS.get().expect("metrics global should be initialized");
+        let mut retries = 0;
+        let mut halted = false;
+        // The skip requests made before halting on a bad object, if it did
+        let mut halted_on_bad_object_at = None;
+        let mut bad_object_policy = self.retry.bad_object_policy;
+        loop {
+            self.control.wait_until_resumed(&self.cancel_token).await?;
+            if std::mem::take(&mut halted) {
+                metrics.sync_halted.set(0);
+            }
+            if halted_on_bad_object_at.is_some_and(|skips| self.control.skip_requests() > skips) {
+                info!("skipping the bad objects of checkpoint {sequence_number} as requested");
+                bad_object_policy = BadObjectPolicy::Skip;
+            }
 
-        let mut created_objects = Vec::new();
-        let mut deleted_addresses = Vec::new();
-        for checkpoint_tx in checkpoint.transactions.iter() {
-            if self.tx_touches_stardust_objects(checkpoint_tx)? {
-                deleted_addresses.extend(
-                    checkpoint_tx
-                        .removed_objects_pre_version()
-                        .filter_map(|obj| obj.is_shared().then_some(IotaAddress::from(obj.id()))),
-                );
-                created_objects.extend(
-                    checkpoint_tx
-                        .output_objects
-                        .iter()
-                        .filter(|obj| obj.is_shared())
-                        .filter_map(|obj| StoredObject::try_from(obj.clone()).ok()),
-                );
+            let Err(e) = self
+                .try_process_checkpoint(&checkpoint, bad_object_policy)
+                .await
+            else {
+                return Ok(());
+            };
+            metrics.checkpoint_failures_count.inc();
+
+            // Retrying does not help with objects that cannot be indexed
+            if e.is::<BadObject>() || retries >= self.retry.checkpoint_max_retries {
+                // Read before halting, not to miss a request made right after
+                halted_on_bad_object_at = e.is::<BadObject>().then(|| self.control.skip_requests());
+                self.halt(sequence_number, &e);
+                halted = true;
+                retries = 0;
+                continue;
+            }
+
+            retries += 1;
+            let backoff = self.retry.backoff(retries);
+            warn!(
+                "failed to process checkpoint {sequence_number}: {e:#}, retry {retries} in {backoff:?}"
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = self.cancel_token.cancelled() => return Err(e),
             }
         }
+    }
+}
 
-        let checkpoint_timestamp = checkpoint.checkpoint_summary.timestamp_ms;
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use iota_types::object::Owner;
 
-        LATEST_CHECKPOINT_UNIX_TIMESTAMP_MS
-            .get_or_init(|| AtomicU64::new(0))
-            .store(checkpoint_timestamp, std::sync::atomic::Ordering::SeqCst);
+    use super::*;
+    use crate::{
+        db::Name,
+        metrics::Metrics,
+        rest::routes::{test_utils::create_basic_output, v1::load_stored_objects},
+    };
 
-        if !created_objects.is_empty() {
-            self.multi_insert_as_database_transactions(created_objects)?;
+    fn new_pool(test_db: &str) -> ConnectionPool {
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
         }
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        pool
+    }
+
+    fn new_worker(pool: &ConnectionPool) -> CheckpointWorker {
+        METRICS.get_or_init(|| Arc::new(Metrics::new(&prometheus::Registry::new())));
+        CheckpointWorker::new(
+            pool.clone(),
+            iota_types::STARDUST_PACKAGE_ID,
+            None,
+            Default::default(),
+            CancellationToken::new(),
+            Default::default(),
+            FailureStore::new(pool.clone()),
+        )
+    }
+
+    #[test]
+    fn write_many_objects() {
+        let test_db = "write_many_objects.db";
+        let pool = new_pool(test_db);
+        let worker = new_worker(&pool);
+
+        // More objects than SQLite can bind IDs in a single query
+        let owner_address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let created_objects = (0..40_000)
+            .map(|i| {
+                StoredObject::new_basic_for_testing(create_basic_output(owner_address, 1, i))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let object_ids = created_objects
+            .iter()
+            .map(|stored_object| stored_object.id)
+            .collect::<Vec<_>>();
+        write_checkpoints(
+            &pool,
+            vec![CheckpointChanges {
+                sequence_number: 1,
+                created_objects,
+                deleted_addresses: vec![],
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            worker.stored_addresses(&object_ids).unwrap(),
+            HashSet::from([owner_address])
+        );
+
+        write_checkpoints(
+            &pool,
+            vec![CheckpointChanges {
+                sequence_number: 2,
+                created_objects: vec![],
+                deleted_addresses: object_ids,
+            }],
+        )
+        .unwrap();
+        let count: i64 = objects
+            .count()
+            .get_result(&mut pool.get_connection().unwrap())
+            .unwrap();
+        assert_eq!(count, 0);
+
+        drop(worker);
+        drop(pool);
+        std::fs::remove_file(test_db).unwrap();
+    }
+
+    #[test]
+    fn transfer_address_owned_outputs() {
+        let test_db = "transfer_address_owned_outputs.db";
+        let pool = new_pool(test_db);
+        let worker = new_worker(&pool);
+
+        // Write the changes of the transactions, given by their output objects,
+        // as a single checkpoint
+        let apply = |sequence_number, transactions: &[Vec<Object>]| {
+            let mut changes = NetChanges::default();
+            for output_objects in transactions {
+                worker
+                    .collect_changes(
+                        sequence_number,
+                        "digest",
+                        output_objects,
+                        std::iter::empty(),
+                        &mut changes,
+                        BadObjectPolicy::Halt,
+                    )
+                    .unwrap();
+            }
+            write_checkpoints(
+                &pool,
+                vec![CheckpointChanges {
+                    sequence_number,
+                    created_objects: changes.created_objects,
+                    deleted_addresses: changes.deleted_addresses,
+                }],
+            )
+            .unwrap();
+        };
+        let owned_outputs = |address| {
+            load_stored_objects(
+                &mut pool.get_connection().unwrap(),
+                address,
+                &Default::default(),
+                ObjectType::Basic,
+                None,
+                true,
+            )
+            .unwrap()
+        };
+
+        let sender = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let recipient = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = iota_types::stardust::output::basic::BasicOutput {
+            expiration: None,
+            ..create_basic_output(sender, 1, 0)
+        };
+        let owned_by = |object_owner: Owner| {
+            StoredObject::basic_object_for_testing(basic.clone(), object_owner).unwrap()
+        };
+        let sent = owned_by(Owner::AddressOwner(sender));
+        let received = owned_by(Owner::AddressOwner(recipient));
+        let wrapped = owned_by(Owner::ObjectOwner(recipient));
+
+        apply(1, &[vec![sent.clone()]]);
+        assert_eq!(owned_outputs(sender).len(), 1);
+
+        // A plain transfer does not call the stardust package
+        assert!(worker.touches_stardust_outputs(
+            std::slice::from_ref(&sent),
+            std::slice::from_ref(&received)
+        ));
+        apply(2, &[vec![received.clone()]]);
+        assert!(owned_outputs(sender).is_empty());
+        assert_eq!(owned_outputs(recipient).len(), 1);
+
+        // Outputs transferred to an object are no longer indexed
+        apply(3, &[vec![wrapped.clone()]]);
+        assert!(owned_outputs(recipient).is_empty());
+
+        // Only the latest change of a checkpoint is kept
+        apply(4, &[vec![received], vec![wrapped], vec![sent]]);
+        assert!(owned_outputs(recipient).is_empty());
+        assert_eq!(owned_outputs(sender).len(), 1);
+
+        drop(worker);
+        drop(pool);
+        std::fs::remove_file(test_db).unwrap();
+    }
 
-        if !deleted_addresses.is_empty() {
-            self.delete_objects(deleted_addresses)?;
+    #[test]
+    fn undecodable_outputs() {
+        let test_db = "undecodable_outputs.db";
+        let progress_store_db = "undecodable_outputs_progress_store.db";
+        let pool = new_pool(test_db);
+        if Path::new(progress_store_db).exists() {
+            std::fs::remove_file(progress_store_db).unwrap();
         }
+        let progress_store_pool = ConnectionPool::new_with_url(
+            progress_store_db,
+            Default::default(),
+            Name::ProgressStore,
+        )
+        .unwrap();
+        progress_store_pool.run_migrations().unwrap();
+        let worker = CheckpointWorker {
+            failures: FailureStore::new(progress_store_pool.clone()),
+            ..new_worker(&pool)
+        };
 
-        METRICS
-            .get()
-            .expect("metrics global should be initialized")
-            .last_checkpoint_indexed
-            .set(checkpoint.checkpoint_summary.sequence_number as i64);
+        let collect = |output_objects: &[Object], bad_object_policy| {
+            let mut changes = NetChanges::default();
+            worker
+                .collect_changes(
+                    1,
+                    "digest",
+                    output_objects,
+                    std::iter::empty(),
+                    &mut changes,
+                    bad_object_policy,
+                )
+                .map(|_| changes)
+        };
 
-        Ok(())
+        let address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let basic = create_basic_output(address, 1, 0);
+        let decodable =
+            StoredObject::basic_object_for_testing(basic.clone(), Owner::AddressOwner(address))
+                .unwrap();
+        let changes = collect(std::slice::from_ref(&decodable), BadObjectPolicy::Halt).unwrap();
+        write_checkpoints(
+            &pool,
+            vec![CheckpointChanges {
+                sequence_number: 1,
+                created_objects: changes.created_objects,
+                deleted_addresses: changes.deleted_addresses,
+            }],
+        )
+        .unwrap();
+
+        // The same output, no longer decodable
+        let mut truncated = bcs::to_bytes(&basic).unwrap();
+        truncated.truncate(40);
+        let undecodable = StoredObject::basic_object_with_contents_for_testing(
+            truncated,
+            Owner::AddressOwner(address),
+        )
+        .unwrap();
+        assert_eq!(undecodable.id(), decodable.id());
+
+        let error = collect(std::slice::from_ref(&undecodable), BadObjectPolicy::Halt).unwrap_err();
+        assert!(error.is::<BadObject>());
+
+        // Skipped, recorded and dropped from the index
+        let changes = collect(std::slice::from_ref(&undecodable), BadObjectPolicy::Skip).unwrap();
+        assert!(changes.created_objects.is_empty());
+        assert_eq!(
+            changes.deleted_addresses,
+            [IotaAddress::from(undecodable.id())]
+        );
+        let failures = worker.failures.latest(10).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].object_id, Some(undecodable.id().to_string()));
+        assert_eq!(failures[0].transaction_digest.as_deref(), Some("digest"));
+        assert_eq!(failures[0].action, "skipped");
+
+        write_checkpoints(
+            &pool,
+            vec![CheckpointChanges {
+                sequence_number: 2,
+                created_objects: changes.created_objects,
+                deleted_addresses: changes.deleted_addresses,
+            }],
+        )
+        .unwrap();
+        let count: i64 = objects
+            .count()
+            .get_result(&mut pool.get_connection().unwrap())
+            .unwrap();
+        assert_eq!(count, 0);
+
+        drop(worker);
+        drop(pool);
+        drop(progress_store_pool);
+        std::fs::remove_file(test_db).unwrap();
+        std::fs::remove_file(progress_store_db).unwrap();
     }
 }
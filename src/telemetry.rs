@@ -0,0 +1,305 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Logs and traces of the service, optionally exported to an OpenTelemetry
+//! collector through OTLP.
+//!
+//! The log filter can be changed at runtime through the admin API.
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use opentelemetry::{KeyValue, trace::TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    Resource, runtime,
+    trace::{Sampler, TracerProvider},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{Level, error};
+use tracing_subscriber::{
+    EnvFilter, Registry, filter::LevelFilter, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+/// Handle to change the log filter of the global subscriber at runtime.
+pub(crate) static LOG_FILTER: OnceLock<LogFilterHandle> = OnceLock::new();
+
+/// The format of the logs.
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => f.write_str("text"),
+            Self::Json => f.write_str("json"),
+        }
+    }
+}
+
+/// Settings of the logs and of the trace export.
+#[derive(Args, Debug, Clone)]
+pub struct TracingConfig {
+    /// The format of the logs.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, env = "LOG_FORMAT")]
+    pub log_format: LogFormat,
+    /// Comma-separated filter directives overriding the log level of some
+    /// targets, e.g. `iota_data_ingestion_core=warn`.
+    #[arg(long, env = "LOG_FILTER")]
+    pub log_filter: Option<String>,
+    /// OTLP gRPC endpoint of the collector to export the traces to, e.g.
+    /// `http://localhost:4317`. The traces are not exported if not set.
+    #[arg(long, env = "OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    /// Service name of the exported traces.
+    #[arg(long, default_value = env!("CARGO_PKG_NAME"), env = "OTLP_SERVICE_NAME")]
+    pub otlp_service_name: String,
+    /// Ratio of the traces to export, between 0 and 1.
+    #[arg(long, value_parser = parse_ratio, default_value = "1.0")]
+    #[arg(env = "OTLP_SAMPLING_RATIO")]
+    pub otlp_sampling_ratio: f64,
+}
+
+fn parse_ratio(arg: &str) -> Result<f64> {
+    let ratio = arg.parse::<f64>()?;
+    anyhow::ensure!((0.0..=1.0).contains(&ratio), "must be between 0 and 1");
+    Ok(ratio)
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            log_format: Default::default(),
+            log_filter: None,
+            otlp_endpoint: None,
+            otlp_service_name: env!("CARGO_PKG_NAME").to_owned(),
+            otlp_sampling_ratio: 1.0,
+        }
+    }
+}
+
+/// Flushes the exported traces when dropped.
+#[must_use]
+pub(crate) struct TracingGuard {
+    tracer_provider: Option<TracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            if let Err(e) = tracer_provider.shutdown() {
+                error!("failed to flush the traces: {e}");
+            }
+        }
+    }
+}
+
+/// Changes the log filter of a subscriber.
+#[derive(Debug)]
+pub(crate) struct LogFilterHandle(reload::Handle<EnvFilter, Registry>);
+
+impl LogFilterHandle {
+    /// Get the directives of the current filter.
+    pub(crate) fn current(&self) -> Result<String> {
+        Ok(self.0.with_current(ToString::to_string)?)
+    }
+
+    /// Replace the filter, rejecting invalid directives.
+    pub(crate) fn set(&self, directives: &str) -> Result<()> {
+        let filter = EnvFilter::builder()
+            .parse(directives)
+            .with_context(|| format!("invalid log filter {directives}"))?;
+        Ok(self.0.reload(filter)?)
+    }
+}
+
+/// Build the log filter from the default level and the directives
+/// overriding it.
+fn log_filter(log_level: Level, directives: Option<&str>) -> Result<EnvFilter> {
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::from_level(log_level).into())
+        .parse(directives.unwrap_or_default())
+        .context("invalid log filter")
+}
+
+/// Install the global tracing subscriber, logging to the standard output and
+/// exporting the spans to the OTLP collector if configured.
+///
+/// It must be called within a Tokio runtime.
+pub(crate) fn init_tracing(log_level: Level, config: &TracingConfig) -> Result<TracingGuard> {
+    let (filter, filter_handle) =
+        reload::Layer::new(log_filter(log_level, config.log_filter.as_deref())?);
+    let json = config.log_format == LogFormat::Json;
+
+    let tracer_provider = config
+        .otlp_endpoint
+        .as_deref()
+        .map(|endpoint| tracer_provider(endpoint, config))
+        .transpose()?;
+    let otel_layer = tracer_provider.as_ref().map(|tracer_provider| {
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(|| tracing_subscriber::fmt::layer().json()))
+        .with(otel_layer)
+        .try_init()
+        .context("setting default subscriber failed")?;
+    LOG_FILTER.get_or_init(|| LogFilterHandle(filter_handle));
+
+    Ok(TracingGuard { tracer_provider })
+}
+
+/// Build the provider of the tracers exporting the spans in batches to the
+/// collector.
+fn tracer_provider(endpoint: &str, config: &TracingConfig) -> Result<TracerProvider> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .context("failed to build the OTLP exporter")?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.otlp_sampling_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.otlp_service_name.clone(),
+        )]))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use opentelemetry_proto::tonic::{
+        collector::trace::v1::{
+            ExportTraceServiceRequest, ExportTraceServiceResponse,
+            trace_service_server::{TraceService, TraceServiceServer},
+        },
+        common::v1::any_value::Value,
+    };
+    use tokio::sync::mpsc;
+    use tonic::{
+        Request, Response, Status,
+        transport::{Server, server::TcpIncoming},
+    };
+
+    use super::*;
+
+    #[test]
+    fn change_log_filter() {
+        let (filter, handle) = reload::Layer::new(log_filter(Level::INFO, None).unwrap());
+        let handle = LogFilterHandle(handle);
+        let subscriber = tracing_subscriber::registry().with(filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(handle.current().unwrap(), "info");
+            assert!(!tracing::enabled!(target: "rebased_stardust_indexer::sync", Level::DEBUG));
+
+            handle
+                .set("warn,rebased_stardust_indexer::sync=debug")
+                .unwrap();
+            assert!(tracing::enabled!(target: "rebased_stardust_indexer::sync", Level::DEBUG));
+            assert!(!tracing::enabled!(target: "iota_data_ingestion_core", Level::INFO));
+
+            // Invalid directives keep the current filter
+            assert!(handle.set("sync=verbose").is_err());
+            assert!(tracing::enabled!(target: "rebased_stardust_indexer::sync", Level::DEBUG));
+        });
+
+        assert!(log_filter(Level::INFO, Some("iota_data_ingestion_core=warn")).is_ok());
+        assert!(log_filter(Level::INFO, Some("=debug=")).is_err());
+    }
+
+    /// Stands in for an OTLP collector, forwarding the received spans.
+    struct Collector(mpsc::UnboundedSender<ExportTraceServiceRequest>);
+
+    #[tonic::async_trait]
+    impl TraceService for Collector {
+        async fn export(
+            &self,
+            request: Request<ExportTraceServiceRequest>,
+        ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+            self.0.send(request.into_inner()).ok();
+            Ok(Response::new(ExportTraceServiceResponse {
+                partial_success: None,
+            }))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn export_spans_to_collector() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let collector_address = listener.local_addr().unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(
+            Server::builder()
+                .add_service(TraceServiceServer::new(Collector(sender)))
+                .serve_with_incoming(TcpIncoming::from_listener(listener, true, None).unwrap()),
+        );
+
+        let config = TracingConfig {
+            otlp_endpoint: Some(format!("http://{collector_address}")),
+            otlp_service_name: "test-indexer".to_owned(),
+            ..Default::default()
+        };
+        let tracer_provider =
+            tracer_provider(config.otlp_endpoint.as_deref().unwrap(), &config).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("process_checkpoint", sequence_number = 42).in_scope(|| {
+                tracing::info_span!("write_checkpoints").in_scope(|| {});
+            });
+        });
+        tokio::task::spawn_blocking(move || tracer_provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let request = tokio::time::timeout(Duration::from_secs(10), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let resource_spans = &request.resource_spans[0];
+        let service_name = resource_spans
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == "service.name")
+            .and_then(|attribute| attribute.value.as_ref()?.value.clone());
+        assert_eq!(
+            service_name,
+            Some(Value::StringValue("test-indexer".to_owned()))
+        );
+
+        let spans = resource_spans
+            .scope_spans
+            .iter()
+            .flat_map(|scope_spans| &scope_spans.spans)
+            .collect::<Vec<_>>();
+        let parent = spans
+            .iter()
+            .find(|span| span.name == "process_checkpoint")
+            .unwrap();
+        let child = spans
+            .iter()
+            .find(|span| span.name == "write_checkpoints")
+            .unwrap();
+        assert_eq!(child.parent_span_id, parent.span_id);
+        assert_eq!(child.trace_id, parent.trace_id);
+    }
+}
@@ -0,0 +1,541 @@
+// Copyright (c) 2024 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Integrity verification of the objects database.
+//!
+//! The `objects` table is the source of truth, while the
+//! `expiration_unlock_conditions` and `basic_output_features` tables are
+//! derived from the decoded contents of the objects, and can therefore be
+//! repaired from them. Outputs without expiration unlock condition, or without
+//! sender and tag, have no row in them.
+use std::fmt;
+
+use anyhow::Result;
+use diesel::prelude::*;
+
+use crate::{
+    db::ConnectionPool,
+    models::{
+        BasicOutputFeatures, ExpirationUnlockCondition, IotaAddress, ObjectType, StoredObject,
+    },
+    schema::{basic_output_features, expiration_unlock_conditions, objects},
+};
+
+/// Number of objects checked per database query.
+const BATCH_SIZE: i64 = 1000;
+
+/// An inconsistency found in the objects database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// The contents do not decode into the type given by `object_type`.
+    UndecodableContents {
+        object_id: IotaAddress,
+        object_type: ObjectType,
+        error: String,
+    },
+    /// The UID of the decoded output differs from `objects.id`.
+    IdMismatch {
+        object_id: IotaAddress,
+        decoded_id: IotaAddress,
+    },
+    /// The object has no matching expiration unlock condition row.
+    MissingExpirationUnlockCondition {
+        object_id: IotaAddress,
+        decoded: ExpirationUnlockCondition,
+    },
+    /// The expiration unlock condition row differs from the decoded output.
+    ExpirationUnlockConditionMismatch {
+        object_id: IotaAddress,
+        stored: ExpirationUnlockCondition,
+        decoded: ExpirationUnlockCondition,
+    },
+    /// The object has an expiration unlock condition row, while the decoded
+    /// output has no such condition.
+    UnexpectedExpirationUnlockCondition { object_id: IotaAddress },
+    /// The expiration unlock condition row has no matching object.
+    OrphanExpirationUnlockCondition { object_id: IotaAddress },
+    /// The features row differs from the sender and tag of the decoded basic
+    /// output, or is missing or unexpected.
+    BasicOutputFeaturesMismatch {
+        object_id: IotaAddress,
+        stored: Option<BasicOutputFeatures>,
+        decoded: Option<BasicOutputFeatures>,
+    },
+    /// The features row has no matching object.
+    OrphanBasicOutputFeatures { object_id: IotaAddress },
+}
+
+impl Issue {
+    /// Whether the issue can be fixed by rebuilding the derived tables.
+    pub fn is_repairable(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingExpirationUnlockCondition { .. }
+                | Self::ExpirationUnlockConditionMismatch { .. }
+                | Self::UnexpectedExpirationUnlockCondition { .. }
+                | Self::OrphanExpirationUnlockCondition { .. }
+                | Self::BasicOutputFeaturesMismatch { .. }
+                | Self::OrphanBasicOutputFeatures { .. }
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndecodableContents {
+                object_id,
+                object_type,
+                error,
+            } => write!(
+                f,
+                "object {} cannot be decoded as {object_type:?}: {error}",
+                object_id.0
+            ),
+            Self::IdMismatch {
+                object_id,
+                decoded_id,
+            } => write!(
+                f,
+                "object {} decodes to a different id {}",
+                object_id.0, decoded_id.0
+            ),
+            Self::MissingExpirationUnlockCondition { object_id, .. } => write!(
+                f,
+                "object {} has no expiration unlock condition",
+                object_id.0
+            ),
+            Self::ExpirationUnlockConditionMismatch {
+                object_id,
+                stored,
+                decoded,
+            } => write!(
+                f,
+                "object {} has expiration unlock condition {stored:?}, expected {decoded:?}",
+                object_id.0
+            ),
+            Self::UnexpectedExpirationUnlockCondition { object_id } => write!(
+                f,
+                "object {} has an expiration unlock condition, expected none",
+                object_id.0
+            ),
+            Self::OrphanExpirationUnlockCondition { object_id } => write!(
+                f,
+                "expiration unlock condition for missing object {}",
+                object_id.0
+            ),
+            Self::BasicOutputFeaturesMismatch {
+                object_id,
+                stored,
+                decoded,
+            } => write!(
+                f,
+                "object {} has features {stored:?}, expected {decoded:?}",
+                object_id.0
+            ),
+            Self::OrphanBasicOutputFeatures { object_id } => {
+                write!(f, "features for missing object {}", object_id.0)
+            }
+        }
+    }
+}
+
+/// The outcome of a database verification.
+#[derive(Clone, Debug, Default)]
+pub struct VerificationReport {
+    pub objects_checked: usize,
+    pub issues: Vec<Issue>,
+    /// Number of issues fixed, only non-zero in repair mode.
+    pub repaired: usize,
+}
+
+impl VerificationReport {
+    /// Number of issues that are still present in the database.
+    pub fn unresolved(&self) -> usize {
+        self.issues.len() - self.repaired
+    }
+}
+
+/// Check that the objects database is self-consistent.
+///
+/// If `repair` is set, the `expiration_unlock_conditions` and
+/// `basic_output_features` rows are rebuilt from the decoded objects and the
+/// orphan or unexpected ones are removed.
+pub fn verify_database(pool: &ConnectionPool, repair: bool) -> Result<VerificationReport> {
+    let mut conn = pool.get_connection()?;
+    let mut report = VerificationReport::default();
+    let mut last_id: Option<IotaAddress> = None;
+
+    loop {
+        let mut query = objects::table
+            .select(StoredObject::as_select())
+            .order(objects::id)
+            .limit(BATCH_SIZE)
+            .into_boxed();
+        if let Some(last_id) = last_id {
+            query = query.filter(objects::id.gt(last_id));
+        }
+        let batch = query.load::<StoredObject>(&mut conn)?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        last_id = Some(last.id);
+        report.objects_checked += batch.len();
+
+        let stored_conditions = expiration_unlock_conditions::table
+            .select(ExpirationUnlockCondition::as_select())
+            .filter(
+                expiration_unlock_conditions::object_id
+                    .eq_any(batch.iter().map(|object| object.id).collect::<Vec<_>>()),
+            )
+            .load::<ExpirationUnlockCondition>(&mut conn)?;
+        let stored_features = basic_output_features::table
+            .select(BasicOutputFeatures::as_select())
+            .filter(
+                basic_output_features::object_id
+                    .eq_any(batch.iter().map(|object| object.id).collect::<Vec<_>>()),
+            )
+            .load::<BasicOutputFeatures>(&mut conn)?;
+
+        let mut repairs = Vec::new();
+        let mut removals = Vec::new();
+        let mut feature_repairs = Vec::new();
+        let mut feature_removals = Vec::new();
+        for stored_object in batch {
+            let stored = stored_conditions
+                .iter()
+                .find(|condition| condition.object_id == stored_object.id);
+            let features = stored_features
+                .iter()
+                .find(|features| features.object_id == stored_object.id);
+            for issue in check_object(&stored_object, stored, features) {
+                match &issue {
+                    Issue::MissingExpirationUnlockCondition { decoded, .. }
+                    | Issue::ExpirationUnlockConditionMismatch { decoded, .. } => {
+                        repairs.push(decoded.clone())
+                    }
+                    Issue::UnexpectedExpirationUnlockCondition { object_id } => {
+                        removals.push(*object_id)
+                    }
+                    Issue::BasicOutputFeaturesMismatch {
+                        decoded: Some(decoded),
+                        ..
+                    } => feature_repairs.push(decoded.clone()),
+                    Issue::BasicOutputFeaturesMismatch {
+                        object_id,
+                        decoded: None,
+                        ..
+                    } => feature_removals.push(*object_id),
+                    _ => {}
+                }
+                report.issues.push(issue);
+            }
+        }
+
+        let repaired =
+            repairs.len() + removals.len() + feature_repairs.len() + feature_removals.len();
+        if repair && repaired > 0 {
+            conn.transaction::<_, anyhow::Error, _>(|conn| {
+                for condition in &repairs {
+                    diesel::insert_into(expiration_unlock_conditions::table)
+                        .values(condition)
+                        .on_conflict(expiration_unlock_conditions::object_id)
+                        .do_update()
+                        .set(condition)
+                        .execute(conn)?;
+                }
+                diesel::delete(expiration_unlock_conditions::table)
+                    .filter(expiration_unlock_conditions::object_id.eq_any(&removals))
+                    .execute(conn)?;
+                for features in &feature_repairs {
+                    diesel::insert_into(basic_output_features::table)
+                        .values(features)
+                        .on_conflict(basic_output_features::object_id)
+                        .do_update()
+                        .set(features)
+                        .execute(conn)?;
+                }
+                diesel::delete(basic_output_features::table)
+                    .filter(basic_output_features::object_id.eq_any(&feature_removals))
+                    .execute(conn)?;
+                Ok(())
+            })?;
+            report.repaired += repaired;
+        }
+    }
+
+    let orphans = expiration_unlock_conditions::table
+        .left_join(objects::table)
+        .filter(objects::id.nullable().is_null())
+        .select(expiration_unlock_conditions::object_id)
+        .load::<IotaAddress>(&mut conn)?;
+
+    if repair && !orphans.is_empty() {
+        report.repaired += diesel::delete(expiration_unlock_conditions::table)
+            .filter(expiration_unlock_conditions::object_id.eq_any(&orphans))
+            .execute(&mut conn)?;
+    }
+    report.issues.extend(
+        orphans
+            .into_iter()
+            .map(|object_id| Issue::OrphanExpirationUnlockCondition { object_id }),
+    );
+
+    let orphans = basic_output_features::table
+        .left_join(objects::table)
+        .filter(objects::id.nullable().is_null())
+        .select(basic_output_features::object_id)
+        .load::<IotaAddress>(&mut conn)?;
+
+    if repair && !orphans.is_empty() {
+        report.repaired += diesel::delete(basic_output_features::table)
+            .filter(basic_output_features::object_id.eq_any(&orphans))
+            .execute(&mut conn)?;
+    }
+    report.issues.extend(
+        orphans
+            .into_iter()
+            .map(|object_id| Issue::OrphanBasicOutputFeatures { object_id }),
+    );
+
+    Ok(report)
+}
+
+/// Check a single object against its stored expiration unlock condition and
+/// features.
+fn check_object(
+    stored_object: &StoredObject,
+    stored: Option<&ExpirationUnlockCondition>,
+    stored_features: Option<&BasicOutputFeatures>,
+) -> Vec<Issue> {
+    let object_id = stored_object.id;
+    let object_type = stored_object.object_type;
+    let undecodable = |e: anyhow::Error| Issue::UndecodableContents {
+        object_id,
+        object_type,
+        error: e.to_string(),
+    };
+
+    let decoded_id = match stored_object.output_id() {
+        Ok(decoded_id) => decoded_id,
+        Err(e) => return vec![undecodable(e)],
+    };
+    if decoded_id != object_id {
+        return vec![Issue::IdMismatch {
+            object_id,
+            decoded_id,
+        }];
+    }
+
+    let decoded = match stored_object.expiration_unlock_condition() {
+        Ok(decoded) => decoded,
+        Err(e) => return vec![undecodable(e)],
+    };
+    let decoded_features = match stored_object.basic_output_features() {
+        Ok(decoded_features) => decoded_features,
+        Err(e) => return vec![undecodable(e)],
+    };
+
+    let mut issues = Vec::new();
+    issues.extend(match (stored, decoded) {
+        (None, Some(decoded)) => {
+            Some(Issue::MissingExpirationUnlockCondition { object_id, decoded })
+        }
+        (Some(_), None) => Some(Issue::UnexpectedExpirationUnlockCondition { object_id }),
+        (Some(stored), Some(decoded)) if *stored != decoded => {
+            Some(Issue::ExpirationUnlockConditionMismatch {
+                object_id,
+                stored: stored.clone(),
+                decoded,
+            })
+        }
+        _ => None,
+    });
+    if stored_features != decoded_features.as_ref() {
+        issues.push(Issue::BasicOutputFeaturesMismatch {
+            object_id,
+            stored: stored_features.cloned(),
+            decoded: decoded_features,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use diesel::{connection::SimpleConnection, insert_into};
+
+    use super::*;
+    use crate::{
+        db::Name,
+        rest::routes::test_utils::{create_and_insert_basic_output, create_basic_output},
+    };
+
+    #[test]
+    fn verify_and_repair_database() {
+        let test_db = "verify_and_repair_database.db";
+
+        if Path::new(test_db).exists() {
+            std::fs::remove_file(test_db).unwrap();
+        }
+
+        let pool =
+            ConnectionPool::new_with_url(test_db, Default::default(), Name::Objects).unwrap();
+        pool.run_migrations().unwrap();
+        let mut conn = pool.get_connection().unwrap();
+
+        let owner_address = iota_types::base_types::IotaAddress::random_for_testing_only();
+        let outputs = (0..4)
+            .map(|i| create_and_insert_basic_output(&mut conn, owner_address, 100 + i, 100))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let output_id = |i: usize| IotaAddress::from(*outputs[i].id.object_id());
+
+        // Condition not matching the decoded output
+        diesel::update(expiration_unlock_conditions::table.find(output_id(0)))
+            .set(expiration_unlock_conditions::unix_time.eq(1))
+            .execute(&mut conn)
+            .unwrap();
+
+        // Missing condition
+        diesel::delete(expiration_unlock_conditions::table.find(output_id(1)))
+            .execute(&mut conn)
+            .unwrap();
+
+        // Orphan condition
+        conn.batch_execute("PRAGMA foreign_keys = OFF;").unwrap();
+        let orphan_id = IotaAddress(iota_types::base_types::IotaAddress::random_for_testing_only());
+        insert_into(expiration_unlock_conditions::table)
+            .values(&ExpirationUnlockCondition {
+                owner: IotaAddress(owner_address),
+                return_address: IotaAddress(owner_address),
+                unix_time: 100,
+                object_id: orphan_id,
+            })
+            .execute(&mut conn)
+            .unwrap();
+        conn.batch_execute("PRAGMA foreign_keys = ON;").unwrap();
+
+        // Object stored under another id than the decoded one
+        let copied_id = IotaAddress(iota_types::base_types::IotaAddress::random_for_testing_only());
+        let mut copied = objects::table
+            .find(output_id(2))
+            .select(StoredObject::as_select())
+            .first::<StoredObject>(&mut conn)
+            .unwrap();
+        copied.id = copied_id;
+        insert_into(objects::table)
+            .values(&copied)
+            .execute(&mut conn)
+            .unwrap();
+
+        // Object that does not decode
+        let undecodable_id =
+            IotaAddress(iota_types::base_types::IotaAddress::random_for_testing_only());
+        insert_into(objects::table)
+            .values(&StoredObject {
+                id: undecodable_id,
+                object_type: ObjectType::Basic,
+                contents: vec![1, 2, 3],
+                address_owner: None,
+            })
+            .execute(&mut conn)
+            .unwrap();
+
+        // Outputs without expiration unlock condition, one of them with a row
+        let without_expiration = (0..2)
+            .map(|_| {
+                let basic = iota_types::stardust::output::basic::BasicOutput {
+                    expiration: None,
+                    ..create_basic_output(owner_address, 100, 100)
+                };
+                let stored_object = StoredObject::new_basic_for_testing(basic).unwrap();
+                insert_into(objects::table)
+                    .values(&stored_object)
+                    .execute(&mut conn)
+                    .unwrap();
+                stored_object.id
+            })
+            .collect::<Vec<_>>();
+        insert_into(expiration_unlock_conditions::table)
+            .values(&ExpirationUnlockCondition {
+                owner: IotaAddress(owner_address),
+                return_address: IotaAddress(owner_address),
+                unix_time: 100,
+                object_id: without_expiration[1],
+            })
+            .execute(&mut conn)
+            .unwrap();
+
+        // Tagged output without features row
+        let tagged =
+            StoredObject::new_basic_for_testing(iota_types::stardust::output::basic::BasicOutput {
+                tag: Some(b"deposit".to_vec()),
+                ..create_basic_output(owner_address, 100, 100)
+            })
+            .unwrap();
+        insert_into(objects::table)
+            .values(&tagged)
+            .execute(&mut conn)
+            .unwrap();
+        insert_into(expiration_unlock_conditions::table)
+            .values(&tagged.expiration_unlock_condition().unwrap().unwrap())
+            .execute(&mut conn)
+            .unwrap();
+        drop(conn);
+
+        let report = verify_database(&pool, false).unwrap();
+        assert_eq!(report.objects_checked, 9);
+        assert_eq!(report.issues.len(), 7);
+        assert_eq!(report.repaired, 0);
+        assert!(report.issues.iter().any(|issue| matches!(
+            issue,
+            Issue::ExpirationUnlockConditionMismatch { object_id, .. } if *object_id == output_id(0)
+        )));
+        assert!(report.issues.iter().any(|issue| matches!(
+            issue,
+            Issue::MissingExpirationUnlockCondition { object_id, .. } if *object_id == output_id(1)
+        )));
+        assert!(
+            report
+                .issues
+                .contains(&Issue::OrphanExpirationUnlockCondition {
+                    object_id: orphan_id
+                })
+        );
+        assert!(report.issues.contains(&Issue::IdMismatch {
+            object_id: copied_id,
+            decoded_id: output_id(2)
+        }));
+        assert!(report.issues.iter().any(|issue| matches!(
+            issue,
+            Issue::UndecodableContents { object_id, .. } if *object_id == undecodable_id
+        )));
+        assert!(
+            report
+                .issues
+                .contains(&Issue::UnexpectedExpirationUnlockCondition {
+                    object_id: without_expiration[1]
+                })
+        );
+        assert!(report.issues.contains(&Issue::BasicOutputFeaturesMismatch {
+            object_id: tagged.id,
+            stored: None,
+            decoded: tagged.basic_output_features().unwrap(),
+        }));
+
+        let report = verify_database(&pool, true).unwrap();
+        assert_eq!(report.repaired, 5);
+        assert_eq!(report.unresolved(), 2);
+
+        // Only the issues on the objects themselves remain
+        let report = verify_database(&pool, false).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert!(report.issues.iter().all(|issue| !issue.is_repairable()));
+
+        // clean-up test db
+        std::fs::remove_file(test_db).unwrap();
+    }
+}